@@ -1,11 +1,20 @@
 use lsp_types::*;
 use orgize::{export::Traverser, rowan::TextRange, Org, ParseConfig};
+use std::collections::{HashMap, HashSet};
 use std::iter::once;
 
+use crate::command::SessionInfo;
+use crate::utils::eval::Trust;
+use crate::utils::id::IdIndex;
+use crate::utils::language::{ExecuteOutput, Executor, LanguageConfig};
+use crate::utils::library::Library;
+
 pub struct OrgDocument {
     pub text: String,
     pub line_starts: Vec<u32>,
     pub org: Org,
+    /// increased by one on every modification
+    pub version: u32,
 }
 
 impl OrgDocument {
@@ -16,10 +25,13 @@ impl OrgDocument {
             org: config.parse(&text),
             line_starts: line_starts(&text),
             text,
+            version: 0,
         }
     }
 
     pub fn update(&mut self, start: u32, end: u32, text: &str) {
+        self.version += 1;
+
         self.text
             .replace_range((start as usize)..(end as usize), text);
 
@@ -193,6 +205,17 @@ pub trait Backend {
         anyhow::bail!("unimplemented")
     }
 
+    async fn create_dir_all(&self, url: &Url) -> anyhow::Result<()> {
+        let _ = url;
+        anyhow::bail!("unimplemented")
+    }
+
+    /// Sets unix file permission, e.g. `0o755`
+    async fn set_permissions(&self, url: &Url, mode: u32) -> anyhow::Result<()> {
+        let _ = (url, mode);
+        anyhow::bail!("unimplemented")
+    }
+
     fn resolve_in(&self, url: &str, base: &Url) -> anyhow::Result<Url> {
         if let Some(url) = url.strip_prefix("~/") {
             if let Some(home_dir) = self.home_dir() {
@@ -211,6 +234,17 @@ pub trait Backend {
         let _ = (ty, message);
     }
 
+    /// Shows message with given actions, returns the chosen one
+    async fn show_message_request(
+        &self,
+        ty: MessageType,
+        message: String,
+        actions: Vec<String>,
+    ) -> anyhow::Result<Option<String>> {
+        let _ = (ty, message, actions);
+        Ok(None)
+    }
+
     async fn apply_edit(&self, url: Url, new_text: String, range: TextRange) -> anyhow::Result<()> {
         self.apply_edits(std::iter::once((url, new_text, range)))
             .await
@@ -224,8 +258,31 @@ pub trait Backend {
         anyhow::bail!("unimplemented")
     }
 
-    async fn execute(&self, executable: &str, content: &str) -> anyhow::Result<String> {
-        let _ = (executable, content);
+    async fn execute(&self, executor: &Executor, content: &str) -> anyhow::Result<ExecuteOutput> {
+        let _ = (executor, content);
+        anyhow::bail!("unimplemented")
+    }
+
+    /// Executes content in a long-running interpreter shared by blocks with
+    /// the same `:session` name
+    async fn execute_in_session(
+        &self,
+        session: &str,
+        language: &str,
+        executor: &Executor,
+        content: &str,
+    ) -> anyhow::Result<ExecuteOutput> {
+        let _ = (session, language, executor, content);
+        anyhow::bail!("unimplemented")
+    }
+
+    async fn list_sessions(&self) -> anyhow::Result<Vec<SessionInfo>> {
+        anyhow::bail!("unimplemented")
+    }
+
+    /// Returns false if no session with given name is running
+    async fn kill_session(&self, session: &str) -> anyhow::Result<bool> {
+        let _ = session;
         anyhow::bail!("unimplemented")
     }
 }
@@ -236,11 +293,37 @@ pub struct Documents {
     map: dashmap::DashMap<Url, OrgDocument>,
     #[cfg(not(target_arch = "wasm32"))]
     config: dashmap::RwLock<ParseConfig>,
+    #[cfg(not(target_arch = "wasm32"))]
+    timeout: dashmap::RwLock<Option<u64>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    ids: dashmap::RwLock<IdIndex>,
+    /// documents changed since their ids were indexed, reindexed lazily on
+    /// next `with_ids` so editing doesn't traverse the document on every change
+    #[cfg(not(target_arch = "wasm32"))]
+    stale_ids: dashmap::RwLock<HashSet<Url>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    languages: dashmap::RwLock<HashMap<String, LanguageConfig>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    trust: dashmap::RwLock<Trust>,
+    #[cfg(not(target_arch = "wasm32"))]
+    library: dashmap::RwLock<Library>,
 
     #[cfg(target_arch = "wasm32")]
     map: std::cell::RefCell<std::collections::HashMap<Url, OrgDocument>>,
     #[cfg(target_arch = "wasm32")]
     config: std::cell::RefCell<ParseConfig>,
+    #[cfg(target_arch = "wasm32")]
+    timeout: std::cell::RefCell<Option<u64>>,
+    #[cfg(target_arch = "wasm32")]
+    ids: std::cell::RefCell<IdIndex>,
+    #[cfg(target_arch = "wasm32")]
+    stale_ids: std::cell::RefCell<HashSet<Url>>,
+    #[cfg(target_arch = "wasm32")]
+    languages: std::cell::RefCell<HashMap<String, LanguageConfig>>,
+    #[cfg(target_arch = "wasm32")]
+    trust: std::cell::RefCell<Trust>,
+    #[cfg(target_arch = "wasm32")]
+    library: std::cell::RefCell<Library>,
 }
 
 impl Documents {
@@ -255,6 +338,95 @@ impl Documents {
         }
     }
 
+    /// Seconds before execution is killed, unless set for the language or block
+    pub fn set_default_timeout(&self, timeout: Option<u64>) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.timeout.replace(timeout);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            *self.timeout.write() = timeout;
+        }
+    }
+
+    pub fn default_timeout(&self) -> Option<u64> {
+        #[cfg(target_arch = "wasm32")]
+        {
+            *self.timeout.borrow()
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            *self.timeout.read()
+        }
+    }
+
+    pub fn with_ids<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut IdIndex) -> T,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        let stale = std::mem::take(&mut *self.stale_ids.write());
+        #[cfg(target_arch = "wasm32")]
+        let stale = self.stale_ids.take();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut ids = self.ids.write();
+        #[cfg(target_arch = "wasm32")]
+        let mut ids = self.ids.borrow_mut();
+
+        for url in stale {
+            self.get_map(&url, |doc| ids.reindex(&url, doc));
+        }
+
+        f(&mut ids)
+    }
+
+    /// Marks ids of the document as outdated, it locks neither `ids` nor `map`,
+    /// so it can be called while holding a document
+    fn invalidate_ids(&self, url: &Url) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.stale_ids.write().insert(url.clone());
+        #[cfg(target_arch = "wasm32")]
+        self.stale_ids.borrow_mut().insert(url.clone());
+    }
+
+    /// Language settings from configuration, keyed by language name
+    pub fn with_languages<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut HashMap<String, LanguageConfig>) -> T,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut languages = self.languages.write();
+        #[cfg(target_arch = "wasm32")]
+        let mut languages = self.languages.borrow_mut();
+        f(&mut languages)
+    }
+
+    /// Directories allowed to execute source blocks
+    pub fn with_trust<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut Trust) -> T,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut trust = self.trust.write();
+        #[cfg(target_arch = "wasm32")]
+        let mut trust = self.trust.borrow_mut();
+        f(&mut trust)
+    }
+
+    /// Library of named source blocks shared by all documents
+    pub fn with_library<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut Library) -> T,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut library = self.library.write();
+        #[cfg(target_arch = "wasm32")]
+        let mut library = self.library.borrow_mut();
+        f(&mut library)
+    }
+
     pub fn get_map<F, T>(&self, url: &Url, f: F) -> Option<T>
     where
         F: FnOnce(&OrgDocument) -> T,
@@ -282,7 +454,12 @@ impl Documents {
         let (map, config) = (&self.map, self.config.read().clone());
         #[cfg(target_arch = "wasm32")]
         let (mut map, config) = (self.map.borrow_mut(), self.config.borrow().clone());
-        map.insert(url, OrgDocument::new(text, config));
+
+        let version = map.get(&url).map(|doc| doc.version + 1).unwrap_or_default();
+        let mut doc = OrgDocument::new(text, config);
+        doc.version = version;
+        map.insert(url.clone(), doc);
+        self.invalidate_ids(&url);
     }
 
     pub fn update(&self, url: Url, range: Option<Range>, new_text: impl AsRef<str>) {
@@ -291,13 +468,22 @@ impl Documents {
         #[cfg(target_arch = "wasm32")]
         let (mut map, config) = (self.map.borrow_mut(), self.config.borrow().clone());
 
-        if let (Some(mut doc), Some(range)) = (map.get_mut(&url), range) {
-            let start = doc.offset_of(range.start);
-            let end = doc.offset_of(range.end);
-            doc.update(start, end, new_text.as_ref());
-        } else {
-            map.insert(url, OrgDocument::new(new_text, config));
+        if let Some(range) = range {
+            if let Some(mut doc) = map.get_mut(&url) {
+                let start = doc.offset_of(range.start);
+                let end = doc.offset_of(range.end);
+                doc.update(start, end, new_text.as_ref());
+                self.invalidate_ids(&url);
+                return;
+            }
         }
+
+        // lock guard returned by `get_mut` must be released before calling `insert`
+        let version = map.get(&url).map(|doc| doc.version + 1).unwrap_or_default();
+        let mut doc = OrgDocument::new(new_text, config);
+        doc.version = version;
+        map.insert(url.clone(), doc);
+        self.invalidate_ids(&url);
     }
 
     pub fn len(&self) -> usize {
@@ -1,25 +1,40 @@
 use axum::{
     extract::State,
     http::{Method, StatusCode},
-    response::{IntoResponse, Response},
-    routing::post,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
     Json, Router,
 };
 use clap::Args;
+use futures::{stream, Stream};
+use lsp_types::Url;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
 use std::{
+    collections::HashSet,
+    convert::Infallible,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
 };
+use tokio::sync::broadcast::error::RecvError;
 use tower_http::cors::{Any, CorsLayer};
 
-use crate::command::OrgwiseCommand;
+use crate::cli::environment::DocumentChange;
+use crate::command::id::{load_id_locations, IdUpdateLocations};
+use crate::command::{ClockingStatus, Executable, OrgwiseCommand, VersionConflict};
 use crate::{backend::Backend, cli::environment::CliBackend};
 
 #[derive(Debug, Args)]
 pub struct Command {
     #[arg(short, long)]
     port: Option<u16>,
+    /// File for persisting id locations
+    #[arg(long)]
+    id_locations: Option<PathBuf>,
     path: Vec<PathBuf>,
 }
 
@@ -36,14 +51,34 @@ impl Command {
 
         let backend = CliBackend::new(false);
 
-        for path in &self.path {
-            backend.load_org_file(path);
-        }
+        let urls: Vec<_> = self
+            .path
+            .iter()
+            .filter_map(|path| backend.load_org_file(path))
+            .collect();
 
         log::info!("Loaded {} org file(s)", backend.documents().len());
 
+        if let Some(path) = &self.id_locations {
+            let path = std::env::current_dir()?.join(path);
+
+            let Ok(file) = Url::from_file_path(&path) else {
+                anyhow::bail!("failed to parse {}", path.display());
+            };
+
+            backend
+                .documents()
+                .with_ids(|ids| ids.locations_file = Some(file));
+
+            if let Err(err) = load_id_locations(&backend).await {
+                log::warn!("failed to load id locations: {err:?}");
+            }
+        }
+
         let state = AppState::new(backend);
 
+        let _watcher = watch_org_files(state.clone(), &urls)?;
+
         let cors = CorsLayer::new()
             .allow_methods([Method::GET, Method::POST])
             .allow_headers(Any)
@@ -51,17 +86,67 @@ impl Command {
 
         let app = Router::new()
             .route("/api/command", post(execute_command))
-            .with_state(state)
+            .route("/api/events", get(subscribe_events))
+            .with_state(state.clone())
             .layer(cors);
 
         axum::Server::bind(&addr)
             .serve(app.into_make_service())
+            .with_graceful_shutdown(async {
+                let _ = tokio::signal::ctrl_c().await;
+            })
             .await?;
 
+        if self.id_locations.is_some() {
+            match (IdUpdateLocations {}).execute(state.as_ref()).await {
+                Ok(Some(count)) => log::info!("Saved {count} id location(s)"),
+                Ok(None) => {}
+                Err(err) => log::error!("failed to update id locations: {err:?}"),
+            }
+        }
+
         Ok(())
     }
 }
 
+/// Reloads loaded org files when they are modified by another program
+///
+/// Parent directories are watched instead of the files, since editors may
+/// save a file by renaming a new one over it.
+fn watch_org_files(state: AppState, urls: &[Url]) -> notify::Result<RecommendedWatcher> {
+    // urls of loaded files are canonicalized, and so are paths of events
+    // in their parent directories
+    let files: HashSet<PathBuf> = urls
+        .iter()
+        .filter_map(|url| url.to_file_path().ok())
+        .collect();
+
+    let dirs: HashSet<PathBuf> = files
+        .iter()
+        .filter_map(|file| file.parent())
+        .map(Path::to_path_buf)
+        .collect();
+
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if event.kind.is_modify() || event.kind.is_create() => {
+                for path in event.paths.iter().filter(|path| files.contains(*path)) {
+                    state.reload_org_file(path);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => log::error!("watch error: {err:?}"),
+        })?;
+
+    for dir in &dirs {
+        if let Err(err) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            log::error!("failed to watch {}: {err:?}", dir.display());
+        }
+    }
+
+    Ok(watcher)
+}
+
 async fn execute_command(
     State(state): State<AppState>,
     Json(command): Json<OrgwiseCommand>,
@@ -70,6 +155,10 @@ async fn execute_command(
         .execute_response(state.as_ref())
         .await
         .unwrap_or_else(|err| {
+            if let Some(conflict) = err.downcast_ref::<VersionConflict>() {
+                return (StatusCode::CONFLICT, Json(conflict)).into_response();
+            }
+
             log::error!("{err:?}");
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -78,3 +167,87 @@ async fn execute_command(
                 .into_response()
         })
 }
+
+#[derive(Serialize)]
+struct ChangeEvent {
+    #[serde(flatten)]
+    change: DocumentChange,
+    clocking: <ClockingStatus as Executable>::Result,
+}
+
+/// Streams a `change` event whenever a loaded document is modified,
+/// either by a command or by another program.
+///
+/// A `lagged` event is sent if some changes were dropped,
+/// clients should refetch everything in this case.
+async fn subscribe_events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.subscribe();
+
+    let stream = stream::unfold((state, receiver), |(state, mut receiver)| async move {
+        let event = match receiver.recv().await {
+            Ok(change) => {
+                let clocking = match (ClockingStatus {}).execute(state.as_ref()).await {
+                    Ok(clocking) => clocking,
+                    Err(err) => {
+                        log::error!("{err:?}");
+                        return None;
+                    }
+                };
+
+                Event::default()
+                    .event("change")
+                    .json_data(ChangeEvent { change, clocking })
+                    .unwrap_or_else(|_| Event::default().event("lagged"))
+            }
+            Err(RecvError::Lagged(_)) => Event::default().event("lagged"),
+            Err(RecvError::Closed) => return None,
+        };
+
+        Some((Ok(event), (state, receiver)))
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_watch_org_files() {
+    use axum::body::HttpBody;
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.org");
+    std::fs::write(&path, "* a\n").unwrap();
+
+    let backend = CliBackend::new(false);
+    let url = backend.load_org_file(&path).unwrap();
+
+    let state = AppState::new(backend);
+    let _watcher = watch_org_files(state.clone(), std::slice::from_ref(&url)).unwrap();
+
+    let mut body = subscribe_events(State(state.clone()))
+        .await
+        .into_response()
+        .into_body();
+
+    // save by renaming a new file over it, like most editors do
+    let tmp = dir.path().join("a.org~");
+    std::fs::write(&tmp, "* b\n").unwrap();
+    std::fs::rename(&tmp, &path).unwrap();
+
+    let chunk = tokio::time::timeout(Duration::from_secs(10), body.data())
+        .await
+        .expect("no event after reloading")
+        .unwrap()
+        .unwrap();
+    let chunk = String::from_utf8_lossy(&chunk);
+
+    assert!(chunk.starts_with("event:change\n"));
+    assert!(chunk.contains(url.as_str()));
+    assert_eq!(
+        state.documents().get_map(&url, |doc| doc.text.clone()),
+        Some("* b\n".into())
+    );
+}
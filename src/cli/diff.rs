@@ -0,0 +1,42 @@
+use similar::TextDiff;
+use std::time::Duration;
+
+/// Returns a unified diff between two texts, or `None` if they're identical
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+
+    let diff = TextDiff::configure()
+        // very different inputs fall back to a less minimal diff
+        .timeout(Duration::from_secs(1))
+        .diff_lines(old, new);
+
+    Some(
+        diff.unified_diff()
+            .context_radius(3)
+            .header(old_name, new_name)
+            .to_string(),
+    )
+}
+
+#[test]
+fn test() {
+    assert_eq!(unified_diff("a\n", "a\n", "a", "b"), None);
+
+    assert_eq!(
+        unified_diff(
+            "1\n2\n3\n4\n5\n6\n7\n8\n9\n",
+            "1\n2\n3\n4\nfive\n6\n7\n8\n9\n",
+            "a",
+            "b"
+        )
+        .unwrap(),
+        "--- a\n+++ b\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+    );
+
+    assert_eq!(
+        unified_diff("", "a\nb", "a", "b").unwrap(),
+        "--- a\n+++ b\n@@ -0,0 +1,2 @@\n+a\n+b\n\\ No newline at end of file\n"
+    );
+}
@@ -1,21 +1,116 @@
 use clap::builder::styling::{AnsiColor, Color, Style};
 use lsp_types::{MessageType, Url};
-use orgize::rowan::TextRange;
+use orgize::rowan::{TextRange, TextSize};
+use serde::Serialize;
 use std::{collections::HashMap, fs, path::Path};
+use tokio::sync::broadcast;
 
+use super::executor;
+use super::session::Sessions;
 use crate::backend::{Backend, Documents};
+use crate::command::SessionInfo;
+use crate::utils::headline::headline_lines_in_ranges;
+use crate::utils::language::{ConfigFile, ExecuteOutput, Executor};
 
 pub struct CliBackend {
     dry_run: bool,
     documents: Documents,
+    changes: broadcast::Sender<DocumentChange>,
+    sessions: Sessions,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DocumentChange {
+    pub url: Url,
+    // one-based
+    pub lines: Vec<u32>,
 }
 
 impl CliBackend {
     pub fn new(dry_run: bool) -> Self {
+        let (changes, _) = broadcast::channel(64);
+
+        let documents = Documents::default();
+        load_config_file(&documents);
+
         CliBackend {
-            documents: Documents::default(),
+            documents,
             dry_run,
+            changes,
+            sessions: Sessions::default(),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DocumentChange> {
+        self.changes.subscribe()
+    }
+
+    fn notify_change(&self, url: &Url, ranges: &[TextRange]) {
+        if self.changes.receiver_count() == 0 {
+            return;
         }
+
+        let lines = self
+            .documents
+            .get_map(url, |doc| headline_lines_in_ranges(doc, ranges))
+            .unwrap_or_default();
+
+        let _ = self.changes.send(DocumentChange {
+            url: url.clone(),
+            lines,
+        });
+    }
+
+    /// Reloads an already loaded org file from disk, e.g. when it's modified by another program
+    pub fn reload_org_file(&self, path: &Path) {
+        let Ok(url) = Url::from_file_path(path) else {
+            return;
+        };
+
+        let Some(old) = self.documents.get_map(&url, |doc| doc.text.clone()) else {
+            return;
+        };
+
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => {
+                log::error!("failed to read {}: {err:?}", path.display());
+                return;
+            }
+        };
+
+        if old == content {
+            return;
+        }
+
+        let (old, new) = (old.as_bytes(), content.as_bytes());
+
+        let mut prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        while !content.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+
+        let mut suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !content.is_char_boundary(new.len() - suffix) {
+            suffix -= 1;
+        }
+
+        log::debug!("reloaded {}", path.display());
+
+        self.documents.update(url.clone(), None, &content);
+
+        self.notify_change(
+            &url,
+            &[TextRange::new(
+                TextSize::new(prefix as u32),
+                TextSize::new((new.len() - suffix) as u32),
+            )],
+        );
     }
 
     pub fn load_org_file(&self, path: &Path) -> Option<Url> {
@@ -94,6 +189,7 @@ impl Backend for CliBackend {
             let input = tokio::fs::read_to_string(&path).await?;
             let mut output = String::with_capacity(input.len());
             let mut off = 0;
+            let mut changed = Vec::with_capacity(edits.len());
 
             for (range, content) in edits {
                 let start = range.start().into();
@@ -110,7 +206,12 @@ impl Backend for CliBackend {
                     }
                 } else {
                     output += &input[off..start];
+                    let new_start = output.len() as u32;
                     output += &content;
+                    changed.push(TextRange::new(
+                        new_start.into(),
+                        (output.len() as u32).into(),
+                    ));
                 }
 
                 off = end;
@@ -122,6 +223,7 @@ impl Backend for CliBackend {
                 output += &input[off..];
                 tokio::fs::write(&path, &output).await?;
                 self.documents.update(url.clone(), None, &output);
+                self.notify_change(url, &changed);
             }
         }
 
@@ -129,6 +231,10 @@ impl Backend for CliBackend {
     }
 
     async fn write(&self, url: &Url, content: &str) -> anyhow::Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
         if let Ok(path) = url.to_file_path() {
             tokio::fs::write(path, content).await?;
             Ok(())
@@ -145,24 +251,90 @@ impl Backend for CliBackend {
         }
     }
 
-    async fn execute(&self, executable: &str, content: &str) -> anyhow::Result<String> {
-        let dir = tempfile::tempdir()?;
+    async fn create_dir_all(&self, url: &Url) -> anyhow::Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        if let Ok(path) = url.to_file_path() {
+            tokio::fs::create_dir_all(path).await?;
+            Ok(())
+        } else {
+            anyhow::bail!("Cannot convert Url to PathBuf")
+        }
+    }
+
+    async fn set_permissions(&self, url: &Url, mode: u32) -> anyhow::Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let Ok(path) = url.to_file_path() else {
+            anyhow::bail!("Cannot convert Url to PathBuf")
+        };
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).await?;
+            Ok(())
+        }
 
-        let path = dir.path().join(".orgize");
+        #[cfg(not(unix))]
+        {
+            let _ = (path, mode);
+            anyhow::bail!("File permission is only supported on unix")
+        }
+    }
 
-        tokio::fs::write(&path, content).await?;
+    async fn execute(&self, executor: &Executor, content: &str) -> anyhow::Result<ExecuteOutput> {
+        executor::execute(executor, content).await
+    }
 
-        let output = tokio::process::Command::new(executable)
-            .arg(&path)
-            .output()
-            .await?;
+    async fn execute_in_session(
+        &self,
+        session: &str,
+        language: &str,
+        executor: &Executor,
+        content: &str,
+    ) -> anyhow::Result<ExecuteOutput> {
+        self.sessions
+            .execute(session, language, executor, content)
+            .await
+    }
 
-        let output = String::from_utf8_lossy(&output.stdout);
+    async fn list_sessions(&self) -> anyhow::Result<Vec<SessionInfo>> {
+        Ok(self.sessions.list().await)
+    }
 
-        Ok(output.to_string())
+    async fn kill_session(&self, session: &str) -> anyhow::Result<bool> {
+        self.sessions.kill(session).await
     }
 
     fn documents(&self) -> &Documents {
         &self.documents
     }
 }
+
+/// Loads language settings from `orgwise.toml` in user config directory
+pub fn load_config_file(documents: &Documents) {
+    let Some(path) = dirs::config_dir().map(|dir| dir.join("orgwise").join("orgwise.toml")) else {
+        return;
+    };
+
+    let Ok(content) = fs::read_to_string(&path) else {
+        return;
+    };
+
+    match toml::from_str::<ConfigFile>(&content) {
+        Ok(config) => {
+            documents.with_languages(|languages| languages.extend(config.languages));
+            documents.with_trust(|trust| trust.directories.extend(config.trusted_directories));
+            documents.with_library(|library| library.files.extend(config.library_files));
+            if config.timeout.is_some() {
+                documents.set_default_timeout(config.timeout);
+            }
+        }
+        Err(err) => log::error!("failed to parse {}: {err}", path.display()),
+    }
+}
@@ -0,0 +1,118 @@
+use std::process::{Output, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::utils::language::{ExecuteOutput, Executor};
+
+/// Seconds before execution is killed if no timeout is configured, so a
+/// hanging block never blocks the server forever
+pub const DEFAULT_TIMEOUT: u64 = 600;
+
+/// Writes content to a temporary file, builds it if needed, then runs it
+pub async fn execute(executor: &Executor, content: &str) -> anyhow::Result<ExecuteOutput> {
+    let dir = tempfile::tempdir()?;
+
+    let file = if executor.extension.is_empty() {
+        dir.path().join("orgwise")
+    } else {
+        dir.path().join(format!("orgwise.{}", executor.extension))
+    };
+    let output = dir.path().join(if cfg!(windows) {
+        "orgwise-output.exe"
+    } else {
+        "orgwise-output"
+    });
+
+    tokio::fs::write(&file, content).await?;
+
+    let file = file.to_string_lossy();
+    let output = output.to_string_lossy();
+
+    let cwd = match &executor.dir {
+        Some(url) => url
+            .to_file_path()
+            .map_err(|_| anyhow::anyhow!("cannot convert {url} to path"))?,
+        None => dir.path().to_path_buf(),
+    };
+
+    let start = Instant::now();
+
+    if let Some(build) = &executor.build {
+        let args = executor.args(build, &file, &output);
+
+        let mut cmd = command(&args)?;
+        cmd.current_dir(&cwd).envs(executor.env.iter().cloned());
+
+        let result = run(executor, cmd, None).await?;
+
+        if !result.status.success() {
+            anyhow::bail!(
+                "`{}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&result.stderr)
+            );
+        }
+    }
+
+    let mut args = executor.args(&executor.command, &file, &output);
+    args.extend(executor.cmdline.iter().cloned());
+
+    let mut cmd = command(&args)?;
+    cmd.current_dir(&cwd).envs(executor.env.iter().cloned());
+
+    let stdin = executor.stdin.then_some(content);
+
+    let result = run(executor, cmd, stdin).await?;
+
+    Ok(ExecuteOutput {
+        stdout: String::from_utf8_lossy(&result.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&result.stderr).to_string(),
+        status: result.status.code(),
+        duration: start.elapsed(),
+    })
+}
+
+/// Runs the command and collects its output
+///
+/// The process is killed if it exceeds the timeout, or if the returned future
+/// is dropped, e.g. when the request gets cancelled.
+async fn run(executor: &Executor, mut cmd: Command, stdin: Option<&str>) -> anyhow::Result<Output> {
+    let mut child = cmd
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    // written in another task while output is being read, otherwise both
+    // sides block once pipe buffers are full; pipe is closed when done
+    if let (Some(content), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        let content = content.to_string();
+        tokio::spawn(async move {
+            // fails with broken pipe if the process exits without reading everything
+            let _ = pipe.write_all(content.as_bytes()).await;
+        });
+    }
+
+    let timeout = executor.timeout.unwrap_or(DEFAULT_TIMEOUT);
+
+    match tokio::time::timeout(Duration::from_secs(timeout), child.wait_with_output()).await {
+        Ok(output) => Ok(output?),
+        Err(_) => anyhow::bail!("execution timed out after {timeout}s"),
+    }
+}
+
+fn command(args: &[String]) -> anyhow::Result<Command> {
+    let Some((program, args)) = args.split_first() else {
+        anyhow::bail!("empty command")
+    };
+
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    Ok(cmd)
+}
@@ -1,16 +1,97 @@
-use lsp_types::notification::ShowMessage;
-use lsp_types::{notification::LogMessage, request::ApplyWorkspaceEdit};
+use dashmap::DashMap;
+use lsp_types::notification::{LogMessage, Progress, ShowMessage};
+use lsp_types::request::{ApplyWorkspaceEdit, WorkDoneProgressCreate};
 use orgize::rowan::TextRange;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
 use tower_lsp::{jsonrpc::Result, lsp_types::*, Client, LanguageServer, LspService, Server};
 
+use super::environment::load_config_file;
+use super::executor;
+use super::session::Sessions;
 use crate::backend::{Backend, Documents};
+use crate::command::{Executable, IdUpdateLocations, SessionInfo};
 use crate::lsp;
+use crate::utils::language::{ExecuteOutput, Executor};
 
 struct TowerLspBackend {
     client: Client,
     documents: Documents,
+    sessions: Sessions,
+    /// cancellation signals of running executions, keyed by progress token
+    cancellations: DashMap<String, Arc<Notify>>,
+    /// whether client supports `window/workDoneProgress/create`
+    work_done_progress: AtomicBool,
+}
+
+impl TowerLspBackend {
+    /// Reports progress of given execution, which can be cancelled by client
+    ///
+    /// Dropping the execution future kills its process.
+    async fn with_progress<T>(
+        &self,
+        title: String,
+        execution: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        if !self.work_done_progress.load(Ordering::Relaxed) {
+            return execution.await;
+        }
+
+        let token = format!("orgwise-execute-{}", uuid::Uuid::new_v4().simple());
+
+        let created = self
+            .client
+            .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: NumberOrString::String(token.clone()),
+            })
+            .await
+            .is_ok();
+
+        if !created {
+            return execution.await;
+        }
+
+        let notify = Arc::new(Notify::new());
+        self.cancellations.insert(token.clone(), notify.clone());
+
+        self.progress(
+            &token,
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title,
+                cancellable: Some(true),
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        let result = tokio::select! {
+            result = execution => result,
+            _ = notify.notified() => Err(anyhow::anyhow!("execution cancelled")),
+        };
+
+        self.cancellations.remove(&token);
+
+        self.progress(
+            &token,
+            WorkDoneProgress::End(WorkDoneProgressEnd::default()),
+        )
+        .await;
+
+        result
+    }
+
+    async fn progress(&self, token: &str, value: WorkDoneProgress) {
+        self.client
+            .send_notification::<Progress>(ProgressParams {
+                token: NumberOrString::String(token.to_string()),
+                value: ProgressParamsValue::WorkDone(value),
+            })
+            .await;
+    }
 }
 
 impl Backend for TowerLspBackend {
@@ -35,21 +116,84 @@ impl Backend for TowerLspBackend {
         }
     }
 
-    async fn execute(&self, executable: &str, content: &str) -> anyhow::Result<String> {
-        let dir = tempfile::tempdir()?;
+    async fn create_dir_all(&self, url: &Url) -> anyhow::Result<()> {
+        if let Ok(path) = url.to_file_path() {
+            tokio::fs::create_dir_all(path).await?;
+            Ok(())
+        } else {
+            anyhow::bail!("Cannot convert Url to PathBuf")
+        }
+    }
 
-        let path = dir.path().join(".orgize");
+    async fn set_permissions(&self, url: &Url, mode: u32) -> anyhow::Result<()> {
+        let Ok(path) = url.to_file_path() else {
+            anyhow::bail!("Cannot convert Url to PathBuf")
+        };
 
-        tokio::fs::write(&path, content).await?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).await?;
+            Ok(())
+        }
 
-        let output = tokio::process::Command::new(executable)
-            .arg(&path)
-            .output()
-            .await?;
+        #[cfg(not(unix))]
+        {
+            let _ = (path, mode);
+            anyhow::bail!("File permission is only supported on unix")
+        }
+    }
+
+    async fn execute(&self, executor: &Executor, content: &str) -> anyhow::Result<ExecuteOutput> {
+        self.with_progress(
+            format!("Executing `{}`", executor.command.join(" ")),
+            executor::execute(executor, content),
+        )
+        .await
+    }
+
+    async fn execute_in_session(
+        &self,
+        session: &str,
+        language: &str,
+        executor: &Executor,
+        content: &str,
+    ) -> anyhow::Result<ExecuteOutput> {
+        self.with_progress(
+            format!("Executing in session {session:?}"),
+            self.sessions.execute(session, language, executor, content),
+        )
+        .await
+    }
+
+    async fn list_sessions(&self) -> anyhow::Result<Vec<SessionInfo>> {
+        Ok(self.sessions.list().await)
+    }
+
+    async fn kill_session(&self, session: &str) -> anyhow::Result<bool> {
+        self.sessions.kill(session).await
+    }
 
-        let output = String::from_utf8_lossy(&output.stdout);
+    async fn show_message_request(
+        &self,
+        typ: MessageType,
+        message: String,
+        actions: Vec<String>,
+    ) -> anyhow::Result<Option<String>> {
+        let actions = actions
+            .into_iter()
+            .map(|title| MessageActionItem {
+                title,
+                properties: HashMap::new(),
+            })
+            .collect();
+
+        let action = self
+            .client
+            .show_message_request(typ, message, Some(actions))
+            .await?;
 
-        Ok(output.to_string())
+        Ok(action.map(|action| action.title))
     }
 
     async fn log_message(&self, typ: MessageType, message: String) {
@@ -103,6 +247,15 @@ impl Backend for TowerLspBackend {
 #[tower_lsp::async_trait]
 impl LanguageServer for TowerLspBackend {
     async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let work_done_progress = params
+            .capabilities
+            .window
+            .as_ref()
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or_default();
+        self.work_done_progress
+            .store(work_done_progress, Ordering::Relaxed);
+
         Ok(lsp::initialize(self, params).await)
     }
 
@@ -110,7 +263,27 @@ impl LanguageServer for TowerLspBackend {
         lsp::initialized(self).await;
     }
 
+    async fn work_done_progress_cancel(&self, params: WorkDoneProgressCancelParams) {
+        if let NumberOrString::String(token) = params.token {
+            if let Some(notify) = self.cancellations.get(&token) {
+                notify.notify_one();
+            }
+        }
+    }
+
     async fn shutdown(&self) -> Result<()> {
+        if self.documents.with_ids(|ids| ids.locations_file.is_some()) {
+            if let Err(err) = (IdUpdateLocations {}).execute(self).await {
+                self.log_message(
+                    MessageType::ERROR,
+                    format!("Failed to update id locations: {err}"),
+                )
+                .await;
+            }
+        }
+
+        self.sessions.kill_all().await;
+
         self.log_message(MessageType::INFO, "Orgize LSP shutdown".into())
             .await;
         Ok(())
@@ -124,7 +297,9 @@ impl LanguageServer for TowerLspBackend {
         lsp::did_change(self, params)
     }
 
-    async fn did_save(&self, _: DidSaveTextDocumentParams) {}
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        lsp::did_save(self, params);
+    }
 
     async fn did_close(&self, _: DidCloseTextDocumentParams) {}
 
@@ -134,7 +309,9 @@ impl LanguageServer for TowerLspBackend {
 
     async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {}
 
-    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {}
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        lsp::did_change_watched_files(self, params);
+    }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         Ok(lsp::completion(self, params))
@@ -206,9 +383,16 @@ pub async fn start() -> anyhow::Result<()> {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::build(|client| TowerLspBackend {
-        client,
-        documents: Documents::default(),
+    let (service, socket) = LspService::build(|client| {
+        let documents = Documents::default();
+        load_config_file(&documents);
+        TowerLspBackend {
+            client,
+            documents,
+            sessions: Sessions::default(),
+            cancellations: DashMap::new(),
+            work_done_progress: AtomicBool::new(false),
+        }
     })
     .finish();
 
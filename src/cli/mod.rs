@@ -1,5 +1,8 @@
 pub mod api_server;
+pub mod diff;
 pub mod environment;
+pub mod executor;
 pub mod fmt;
 pub mod lsp_server;
+pub mod session;
 pub mod src_block;
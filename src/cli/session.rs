@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+
+use super::executor::DEFAULT_TIMEOUT;
+use crate::command::SessionInfo;
+use crate::utils::language::{ExecuteOutput, Executor};
+
+const PYTHON_DRIVER: &str = r#"
+import sys, traceback
+g = {"__name__": "__main__"}
+buf = []
+while True:
+    line = sys.stdin.readline()
+    if not line:
+        break
+    if line.rstrip("\n") == sys.argv[1]:
+        status = 0
+        try:
+            exec("".join(buf), g)
+        except SystemExit as err:
+            status = err.code if isinstance(err.code, int) else int(err.code is not None)
+        except BaseException:
+            traceback.print_exc()
+            status = 1
+        buf = []
+        print(sys.argv[1], status, flush=True)
+        print(sys.argv[1], file=sys.stderr, flush=True)
+    else:
+        buf.append(line)
+"#;
+
+const NODE_DRIVER: &str = r#"
+const vm = require("vm");
+const ctx = vm.createContext({ ...globalThis, require, console, process });
+const marker = process.argv[process.argv.length - 1];
+let buf = [];
+require("readline").createInterface({ input: process.stdin }).on("line", (line) => {
+  if (line !== marker) return buf.push(line);
+  let status = 0;
+  try {
+    vm.runInContext(buf.join("\n"), ctx);
+  } catch (err) {
+    console.error(String(err && err.stack || err));
+    status = 1;
+  }
+  buf = [];
+  console.log(marker, status);
+  console.error(marker);
+});
+"#;
+
+/// Long-running interpreters for `:session` source blocks, keyed by session name
+#[derive(Default)]
+pub struct Sessions {
+    /// never locked across `.await`, so sessions can be listed and killed
+    /// while one of them is running
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+}
+
+struct Session {
+    language: String,
+    child: Mutex<Child>,
+    /// locked while running, executions in the same session are serialized
+    io: Arc<tokio::sync::Mutex<SessionIo>>,
+}
+
+struct SessionIo {
+    marker: String,
+    /// shells run code directly, instead of a driver script
+    shell: bool,
+    /// set while waiting for output
+    busy: bool,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: BufReader<ChildStderr>,
+}
+
+impl Sessions {
+    /// Feeds content to the named session, starting it if needed, and returns
+    /// its output
+    pub async fn execute(
+        &self,
+        name: &str,
+        language: &str,
+        executor: &Executor,
+        content: &str,
+    ) -> anyhow::Result<ExecuteOutput> {
+        let (session, mut io) = loop {
+            let session = self.get_or_spawn(name, language, executor)?;
+            let io = session.io.clone().lock_owned().await;
+
+            if !io.busy {
+                break (session, io);
+            }
+
+            // previous execution was cancelled before reading its marker
+            self.remove(name, &session);
+        };
+
+        let start = Instant::now();
+
+        let timeout = executor.timeout.unwrap_or(DEFAULT_TIMEOUT);
+
+        let result = tokio::time::timeout(Duration::from_secs(timeout), io.run(content))
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("execution timed out after {timeout}s")));
+
+        match result {
+            Ok((stdout, stderr, status)) => Ok(ExecuteOutput {
+                stdout,
+                stderr,
+                status,
+                duration: start.elapsed(),
+            }),
+            Err(err) => {
+                self.remove(name, &session);
+                Err(err)
+            }
+        }
+    }
+
+    fn map(&self) -> MutexGuard<'_, HashMap<String, Arc<Session>>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn get_or_spawn(
+        &self,
+        name: &str,
+        language: &str,
+        executor: &Executor,
+    ) -> anyhow::Result<Arc<Session>> {
+        let mut sessions = self.map();
+
+        if let Some(session) = sessions.get(name) {
+            if session.language != language {
+                anyhow::bail!(
+                    "session {name:?} is running {:?}, not {language:?}",
+                    session.language
+                );
+            }
+            return Ok(session.clone());
+        }
+
+        let session = Arc::new(Session::spawn(language, executor)?);
+        sessions.insert(name.to_string(), session.clone());
+        Ok(session)
+    }
+
+    /// Kills the session, unless it was already replaced by a new one
+    fn remove(&self, name: &str, session: &Arc<Session>) {
+        let mut sessions = self.map();
+
+        if sessions
+            .get(name)
+            .is_some_and(|existing| Arc::ptr_eq(existing, session))
+        {
+            sessions.remove(name);
+        }
+
+        drop(sessions);
+        session.kill();
+    }
+
+    pub async fn list(&self) -> Vec<SessionInfo> {
+        let mut list: Vec<_> = self
+            .map()
+            .iter()
+            .map(|(name, session)| SessionInfo {
+                name: name.clone(),
+                language: session.language.clone(),
+            })
+            .collect();
+
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+
+    /// Returns false if no session with given name is running
+    pub async fn kill(&self, name: &str) -> anyhow::Result<bool> {
+        let Some(session) = self.map().remove(name) else {
+            return Ok(false);
+        };
+
+        session.kill();
+        Ok(true)
+    }
+
+    pub async fn kill_all(&self) {
+        let sessions = std::mem::take(&mut *self.map());
+
+        for session in sessions.values() {
+            session.kill();
+        }
+    }
+}
+
+impl Session {
+    fn spawn(language: &str, executor: &Executor) -> anyhow::Result<Session> {
+        let marker = format!("__orgwise_session_{}__", uuid::Uuid::new_v4().simple());
+
+        // interpreters are started with drivers reading code from stdin, so
+        // configured executor commands don't apply here
+        let mut cmd = match language {
+            "py" | "python" => {
+                let mut cmd = Command::new("python");
+                cmd.args(["-u", "-c", PYTHON_DRIVER, &marker]);
+                cmd
+            }
+            "js" | "javascript" => {
+                let mut cmd = Command::new("node");
+                cmd.args(["-e", NODE_DRIVER, &marker]);
+                cmd
+            }
+            "sh" | "bash" => {
+                let mut cmd = Command::new("bash");
+                cmd.args(["--noprofile", "--norc"]);
+                cmd
+            }
+            _ => anyhow::bail!("`:session` is not supported for language {language:?}"),
+        };
+
+        if let Some(dir) = executor
+            .dir
+            .as_ref()
+            .and_then(|url| url.to_file_path().ok())
+        {
+            cmd.current_dir(dir);
+        }
+
+        let mut child = cmd
+            .envs(executor.env.iter().cloned())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let (Some(stdin), Some(stdout), Some(stderr)) =
+            (child.stdin.take(), child.stdout.take(), child.stderr.take())
+        else {
+            anyhow::bail!("failed to open stdio of session");
+        };
+
+        Ok(Session {
+            language: language.to_string(),
+            child: Mutex::new(child),
+            io: Arc::new(tokio::sync::Mutex::new(SessionIo {
+                marker,
+                shell: matches!(language, "sh" | "bash"),
+                busy: false,
+                stdin,
+                stdout: BufReader::new(stdout),
+                stderr: BufReader::new(stderr),
+            })),
+        })
+    }
+
+    /// Kills the interpreter, pending execution fails with unexpected exit
+    fn kill(&self) {
+        let _ = self
+            .child
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .start_kill();
+    }
+}
+
+impl SessionIo {
+    /// Returns stdout, stderr and exit status of the content
+    async fn run(&mut self, content: &str) -> anyhow::Result<(String, String, Option<i32>)> {
+        let marker = &self.marker;
+
+        let input = if self.shell {
+            format!("{content}\necho \"{marker} $?\"\necho {marker} >&2\n")
+        } else {
+            format!("{content}\n{marker}\n")
+        };
+
+        self.busy = true;
+
+        let stdin = &mut self.stdin;
+        let write = async move {
+            stdin.write_all(input.as_bytes()).await?;
+            stdin.flush().await?;
+            anyhow::Ok(())
+        };
+
+        // pipes are written and read at the same time, so none of them fills up
+        let (_, (stdout, status), (stderr, _)) = tokio::try_join!(
+            write,
+            read_until_marker(&mut self.stdout, marker),
+            read_until_marker(&mut self.stderr, marker),
+        )?;
+
+        self.busy = false;
+
+        Ok((stdout, stderr, status.parse().ok()))
+    }
+}
+
+/// Reads output until the marker line, returns output and text following the marker
+async fn read_until_marker<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    marker: &str,
+) -> anyhow::Result<(String, String)> {
+    let mut output = String::new();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+
+        if reader.read_line(&mut line).await? == 0 {
+            anyhow::bail!("session exited unexpectedly");
+        }
+
+        // marker may follow output without trailing newline
+        if let Some(idx) = line.find(marker) {
+            output.push_str(&line[..idx]);
+            let rest = line[idx + marker.len()..].trim().to_string();
+            return Ok((output, rest));
+        }
+
+        output.push_str(&line);
+    }
+}
@@ -1,9 +1,14 @@
 use clap::Args;
+use lsp_types::Url;
 use std::path::PathBuf;
 
-use crate::command::{Executable, SrcBlockDetangleAll, SrcBlockExecuteAll, SrcBlockTangleAll};
-
+use super::diff::unified_diff;
 use super::environment::CliBackend;
+use crate::backend::Backend;
+use crate::command::{
+    Executable, SrcBlockDetangleAll, SrcBlockDetangleFromTarget, SrcBlockExecuteAll,
+    SrcBlockTangleAll,
+};
 
 #[derive(Debug, Args)]
 pub struct DetangleCommand {
@@ -11,18 +16,40 @@ pub struct DetangleCommand {
 
     #[arg(short, long)]
     dry_run: bool,
+
+    /// Detangle contents of given tangled file back to its source org files
+    #[arg(long)]
+    from_target: Option<PathBuf>,
 }
 
 impl DetangleCommand {
     pub async fn run(self) -> anyhow::Result<()> {
         let backend = CliBackend::new(self.dry_run);
 
-        for path in self.path {
-            if let Some(url) = backend.load_org_file(&path) {
+        for path in &self.path {
+            if let Some(url) = backend.load_org_file(path) {
                 SrcBlockDetangleAll { url }.execute(&backend).await?;
             }
         }
 
+        // unmatched sections fail the command, so it runs last
+        if let Some(target) = &self.from_target {
+            let url = Url::from_file_path(std::fs::canonicalize(target)?)
+                .map_err(|_| anyhow::anyhow!("failed to parse {}", target.display()))?;
+
+            let report = SrcBlockDetangleFromTarget { url }.execute(&backend).await?;
+
+            println!("{} block(s) updated", report.updated);
+
+            if !report.unmatched.is_empty() {
+                anyhow::bail!(
+                    "{} block(s) can't be matched: {}",
+                    report.unmatched.len(),
+                    report.unmatched.join(", ")
+                );
+            }
+        }
+
         Ok(())
     }
 }
@@ -33,14 +60,32 @@ pub struct ExecuteCommand {
 
     #[arg(short, long)]
     dry_run: bool,
+
+    /// Execute source blocks even if their results are cached
+    #[arg(short, long)]
+    force: bool,
+
+    /// Execute files outside of trusted directories
+    #[arg(short, long)]
+    yes: bool,
 }
 
 impl ExecuteCommand {
     pub async fn run(self) -> anyhow::Result<()> {
         let backend = CliBackend::new(self.dry_run);
+
+        if self.yes {
+            backend.documents().with_trust(|trust| trust.all = true);
+        }
+
         for path in self.path {
             if let Some(url) = backend.load_org_file(&path) {
-                SrcBlockExecuteAll { url }.execute(&backend).await?;
+                SrcBlockExecuteAll {
+                    url,
+                    force: self.force,
+                }
+                .execute(&backend)
+                .await?;
             }
         }
         Ok(())
@@ -51,18 +96,61 @@ impl ExecuteCommand {
 pub struct TangleCommand {
     path: Vec<PathBuf>,
 
+    /// Print diffs instead of writing files
     #[arg(short, long)]
     dry_run: bool,
+
+    /// Exit with non-zero status if tangled files are out of date
+    #[arg(long)]
+    check: bool,
 }
 
 impl TangleCommand {
     pub async fn run(self) -> anyhow::Result<()> {
-        let backend = CliBackend::new(self.dry_run);
+        let dry_run = self.dry_run || self.check;
+        let backend = CliBackend::new(dry_run);
+
+        let mut outdated = 0;
+        let mut failed = 0;
+
         for path in self.path {
-            if let Some(url) = backend.load_org_file(&path) {
-                SrcBlockTangleAll { url }.execute(&backend).await?;
+            // reason is logged by `load_org_file`
+            let Some(url) = backend.load_org_file(&path) else {
+                failed += 1;
+                continue;
+            };
+
+            let files = SrcBlockTangleAll { url, dry_run }.execute(&backend).await?;
+
+            for file in files {
+                let Some(content) = &file.content else {
+                    println!("{} ({} blocks)", file.url, file.blocks);
+                    continue;
+                };
+
+                let path = file
+                    .url
+                    .to_file_path()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|_| file.url.to_string());
+
+                let existing = backend.read_to_string(&file.url).await.unwrap_or_default();
+
+                if let Some(diff) = unified_diff(&existing, content, &path, &path) {
+                    print!("{diff}");
+                    outdated += 1;
+                }
             }
         }
+
+        if self.check && failed > 0 {
+            anyhow::bail!("{failed} org file(s) can't be loaded");
+        }
+
+        if self.check && outdated > 0 {
+            anyhow::bail!("{outdated} tangled file(s) are out of date");
+        }
+
         Ok(())
     }
 }
@@ -8,14 +8,19 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     backend::Backend,
-    command::Executable,
-    utils::{clocking::find_logbook, headline::find_headline, timestamp::FormatInactiveTimestamp},
+    command::{Executable, VersionConflict},
+    utils::{
+        clocking::find_logbook, headline::HeadlineLocator, timestamp::FormatInactiveTimestamp,
+    },
 };
 
 #[derive(Deserialize, Serialize)]
 pub struct ClockingStart {
     pub url: Url,
-    pub line: u32,
+    #[serde(flatten)]
+    pub locator: HeadlineLocator,
+    #[serde(default)]
+    pub version: Option<u32>,
 }
 
 impl Executable for ClockingStart {
@@ -26,9 +31,9 @@ impl Executable for ClockingStart {
     type Result = bool;
 
     async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
-        let Some(Some(headline)) = backend
+        let Some((version, headline)) = backend
             .documents()
-            .get_map(&self.url, |doc| find_headline(&doc, self.line))
+            .get_map(&self.url, |doc| (doc.version, self.locator.find(doc)))
         else {
             backend
                 .log_message(
@@ -40,6 +45,19 @@ impl Executable for ClockingStart {
             return Ok(false);
         };
 
+        VersionConflict::check(&self.url, self.version, version)?;
+
+        let Some(headline) = headline else {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("cannot find headline {}", self.locator),
+                )
+                .await;
+
+            return Ok(false);
+        };
+
         let now = Local::now().naive_local();
 
         let (new_text, text_range) = (move || {
@@ -90,7 +108,8 @@ async fn test() {
 
     ClockingStart {
         url: url.clone(),
-        line: 1,
+        locator: HeadlineLocator::Line { line: 1 },
+        version: None,
     }
     .execute(&backend)
     .await
@@ -98,7 +117,8 @@ async fn test() {
 
     ClockingStart {
         url: url.clone(),
-        line: 1,
+        locator: HeadlineLocator::Line { line: 1 },
+        version: None,
     }
     .execute(&backend)
     .await
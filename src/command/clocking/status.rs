@@ -22,6 +22,7 @@ pub struct Result {
 struct ClockingStatusResult {
     url: Url,
     line: u32,
+    version: u32,
     start: NaiveDateTime,
     title: String,
 }
@@ -51,6 +52,7 @@ impl Executable for ClockingStatus {
                             running = Some(ClockingStatusResult {
                                 url: url.clone(),
                                 line: doc.line_of(hdl.start().into()) + 1,
+                                version: doc.version,
                                 start,
                                 title: hdl.title_raw(),
                             });
@@ -102,6 +104,7 @@ CLOCK: [2000-01-07 Web 00:00]
 
     let r = |day: u32, title: &str, line: u32| ClockingStatusResult {
         line,
+        version: 0,
         start: NaiveDate::from_ymd_opt(2000, 1, day)
             .unwrap()
             .and_hms_opt(0, 0, 0)
@@ -6,14 +6,17 @@ use serde_json::Value;
 
 use crate::{
     backend::Backend,
-    command::Executable,
-    utils::{headline::find_headline, timestamp::FormatInactiveTimestamp},
+    command::{Executable, VersionConflict},
+    utils::{headline::HeadlineLocator, timestamp::FormatInactiveTimestamp},
 };
 
 #[derive(Deserialize, Serialize)]
 pub struct ClockingStop {
     pub url: Url,
-    pub line: u32,
+    #[serde(flatten)]
+    pub locator: HeadlineLocator,
+    #[serde(default)]
+    pub version: Option<u32>,
 }
 
 impl Executable for ClockingStop {
@@ -24,9 +27,9 @@ impl Executable for ClockingStop {
     type Result = Value;
 
     async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<Value> {
-        let Some(headline) = backend
+        let Some((version, headline)) = backend
             .documents()
-            .get_and_then(&self.url, |doc| find_headline(&doc, self.line))
+            .get_map(&self.url, |doc| (doc.version, self.locator.find(doc)))
         else {
             backend
                 .log_message(
@@ -38,6 +41,19 @@ impl Executable for ClockingStop {
             return Ok(Value::Null);
         };
 
+        VersionConflict::check(&self.url, self.version, version)?;
+
+        let Some(headline) = headline else {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("cannot find headline {}", self.locator),
+                )
+                .await;
+
+            return Ok(Value::Null);
+        };
+
         let now = now();
 
         let edits: Vec<_> = (move || {
@@ -124,7 +140,8 @@ CLOCK: {}
 
     ClockingStop {
         url: url.clone(),
-        line: 2,
+        locator: HeadlineLocator::Line { line: 2 },
+        version: None,
     }
     .execute(&backend)
     .await
@@ -7,6 +7,7 @@ use orgize::{
 mod blank_lines;
 mod list;
 mod rule;
+mod table;
 
 pub fn formatting(org: &Org) -> Vec<(TextRange, String)> {
     let mut indent_level = 0;
@@ -36,6 +37,7 @@ pub fn formatting(org: &Org) -> Vec<(TextRange, String)> {
             indent_level -= 1;
         }
         Event::Enter(Container::OrgTable(table)) => {
+            table::format(table.syntax(), &mut edits);
             blank_lines::format(table.syntax(), &mut edits);
         }
         Event::Enter(Container::SpecialBlock(block)) => {
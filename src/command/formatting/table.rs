@@ -0,0 +1,42 @@
+use orgize::{rowan::TextRange, SyntaxNode};
+
+use crate::utils::table::TableRange;
+
+pub fn format(node: &SyntaxNode, edits: &mut Vec<(TextRange, String)>) {
+    let Some(table) = TableRange::new(node) else {
+        return;
+    };
+
+    let formatted = table.replacement(&table.table);
+
+    if formatted != table.original {
+        edits.push((table.range, formatted));
+    }
+}
+
+#[test]
+fn test() {
+    use crate::test_case;
+    use orgize::ast::OrgTable;
+
+    test_case!(
+        OrgTable,
+        "|a|bb|\n|-+-|\n|1|2|\n",
+        format,
+        "| a | bb |\n|---+----|\n| 1 | 2  |\n"
+    );
+
+    test_case!(
+        OrgTable,
+        "| a | bb |\n|---+----|\n| 1 | 2  |\n",
+        format,
+        "| a | bb |\n|---+----|\n| 1 | 2  |\n"
+    );
+
+    test_case!(
+        OrgTable,
+        "| <r> |\n| x |\n| long |",
+        format,
+        "| <r>  |\n|    x |\n| long |"
+    );
+}
@@ -4,13 +4,16 @@ use serde::{Deserialize, Serialize};
 
 use crate::backend::Backend;
 
-use crate::command::Executable;
-use crate::utils::headline::find_headline;
+use crate::command::{Executable, VersionConflict};
+use crate::utils::headline::HeadlineLocator;
 
 #[derive(Deserialize, Serialize)]
 pub struct HeadlineDuplicate {
     pub url: Url,
-    pub line: u32,
+    #[serde(flatten)]
+    pub locator: HeadlineLocator,
+    #[serde(default)]
+    pub version: Option<u32>,
 }
 
 impl Executable for HeadlineDuplicate {
@@ -19,9 +22,9 @@ impl Executable for HeadlineDuplicate {
     type Result = bool;
 
     async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
-        let Some(Some(headline)) = backend
+        let Some((version, headline)) = backend
             .documents()
-            .get_map(&self.url, |doc| find_headline(&doc, self.line))
+            .get_map(&self.url, |doc| (doc.version, self.locator.find(doc)))
         else {
             backend
                 .log_message(
@@ -33,6 +36,19 @@ impl Executable for HeadlineDuplicate {
             return Ok(false);
         };
 
+        VersionConflict::check(&self.url, self.version, version)?;
+
+        let Some(headline) = headline else {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("cannot find headline {}", self.locator),
+                )
+                .await;
+
+            return Ok(false);
+        };
+
         let (new_text, range) = (move || (headline.raw(), TextRange::empty(headline.end())))();
 
         backend.apply_edit(self.url, new_text, range).await?;
@@ -51,8 +67,9 @@ async fn test() {
     backend.documents().insert(url.clone(), "* a\n* b\n * c");
 
     HeadlineDuplicate {
-        line: 1,
+        locator: HeadlineLocator::Line { line: 1 },
         url: url.clone(),
+        version: None,
     }
     .execute(&backend)
     .await
@@ -60,8 +77,9 @@ async fn test() {
     assert_eq!(backend.get(&url), "* a\n* a\n* b\n * c");
 
     HeadlineDuplicate {
-        line: 2,
+        locator: HeadlineLocator::Line { line: 2 },
         url: url.clone(),
+        version: None,
     }
     .execute(&backend)
     .await
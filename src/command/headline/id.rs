@@ -0,0 +1,129 @@
+use lsp_types::{MessageType, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+
+use crate::command::{Executable, VersionConflict};
+use crate::utils::headline::{headline_id, insert_property, HeadlineLocator};
+
+/// Returns the `ID` property of headline, a new UUID will be assigned
+/// if it doesn't have one, just like `org-id-get-create`.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct HeadlineIdGetCreate {
+    pub url: Url,
+    #[serde(flatten)]
+    pub locator: HeadlineLocator,
+    #[serde(default)]
+    pub version: Option<u32>,
+}
+
+impl Executable for HeadlineIdGetCreate {
+    const NAME: &'static str = "headline-id-get-create";
+
+    const TITLE: Option<&'static str> = Some("Get or create ID");
+
+    type Result = Option<String>;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<Option<String>> {
+        let Some((version, headline)) = backend
+            .documents()
+            .get_map(&self.url, |doc| (doc.version, self.locator.find(doc)))
+        else {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("cannot find document with url {}", self.url),
+                )
+                .await;
+
+            return Ok(None);
+        };
+
+        VersionConflict::check(&self.url, self.version, version)?;
+
+        let Some(headline) = headline else {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("cannot find headline {}", self.locator),
+                )
+                .await;
+
+            return Ok(None);
+        };
+
+        let (id, edit) = (move || match headline_id(&headline) {
+            Some(id) => (id, None),
+            None => {
+                let id = new_id();
+                let edit = insert_property(&headline, "ID", &id);
+                (id, Some(edit))
+            }
+        })();
+
+        if let Some((new_text, text_range)) = edit {
+            backend.apply_edit(self.url, new_text, text_range).await?;
+        }
+
+        Ok(Some(id))
+    }
+}
+
+#[cfg(not(test))]
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[cfg(test)]
+fn new_id() -> String {
+    "00000000-0000-4000-8000-000000000000".into()
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test() {
+    use crate::test::TestBackend;
+
+    let backend = TestBackend::default();
+    let url = Url::parse("test://test.org").unwrap();
+    backend.documents().insert(
+        url.clone(),
+        "* a\n** b\nSCHEDULED: <2000-01-01 Sat>\n* c\n:PROPERTIES:\n:ID:       c\n:END:\n",
+    );
+
+    let id = HeadlineIdGetCreate {
+        url: url.clone(),
+        locator: HeadlineLocator::Outline {
+            outline: vec!["a".into(), "b".into()],
+        },
+        version: None,
+    }
+    .execute(&backend)
+    .await
+    .unwrap();
+    assert_eq!(id.as_deref(), Some("00000000-0000-4000-8000-000000000000"));
+    assert_eq!(
+        backend.get(&url),
+        "* a\n** b\nSCHEDULED: <2000-01-01 Sat>\n:PROPERTIES:\n:ID:       00000000-0000-4000-8000-000000000000\n:END:\n* c\n:PROPERTIES:\n:ID:       c\n:END:\n"
+    );
+
+    let id = HeadlineIdGetCreate {
+        url: url.clone(),
+        locator: HeadlineLocator::Id { id: "c".into() },
+        version: None,
+    }
+    .execute(&backend)
+    .await
+    .unwrap();
+    assert_eq!(id.as_deref(), Some("c"));
+
+    let id = HeadlineIdGetCreate {
+        url: url.clone(),
+        locator: HeadlineLocator::Line { line: 100 },
+        version: None,
+    }
+    .execute(&backend)
+    .await
+    .unwrap();
+    assert_eq!(id, None);
+}
@@ -1,6 +1,7 @@
 mod create;
 mod duplicate;
 mod generate_toc;
+mod id;
 mod remove;
 mod search;
 mod update;
@@ -8,6 +9,7 @@ mod update;
 pub use create::HeadlineCreate;
 pub use duplicate::*;
 pub use generate_toc::*;
+pub use id::*;
 pub use remove::*;
 pub use search::*;
 pub use update::*;
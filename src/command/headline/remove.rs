@@ -3,13 +3,16 @@ use serde::{Deserialize, Serialize};
 
 use crate::backend::Backend;
 
-use crate::command::Executable;
-use crate::utils::headline::find_headline;
+use crate::command::{Executable, VersionConflict};
+use crate::utils::headline::HeadlineLocator;
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct HeadlineRemove {
     pub url: Url,
-    pub line: u32,
+    #[serde(flatten)]
+    pub locator: HeadlineLocator,
+    #[serde(default)]
+    pub version: Option<u32>,
 }
 
 impl Executable for HeadlineRemove {
@@ -18,9 +21,9 @@ impl Executable for HeadlineRemove {
     type Result = bool;
 
     async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
-        let Some(headline) = backend
+        let Some((version, headline)) = backend
             .documents()
-            .get_and_then(&self.url, |doc| find_headline(&doc, self.line))
+            .get_map(&self.url, |doc| (doc.version, self.locator.find(doc)))
         else {
             backend
                 .log_message(
@@ -32,6 +35,19 @@ impl Executable for HeadlineRemove {
             return Ok(false);
         };
 
+        VersionConflict::check(&self.url, self.version, version)?;
+
+        let Some(headline) = headline else {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("cannot find headline {}", self.locator),
+                )
+                .await;
+
+            return Ok(false);
+        };
+
         let text_range = (move || headline.text_range())();
 
         backend
@@ -52,8 +68,9 @@ async fn test() {
     backend.documents().insert(url.clone(), "** \n* ");
 
     HeadlineRemove {
-        line: 1,
+        locator: HeadlineLocator::Line { line: 1 },
         url: url.clone(),
+        version: None,
     }
     .execute(&backend)
     .await
@@ -61,8 +78,9 @@ async fn test() {
     assert_eq!(backend.get(&url), "* ");
 
     HeadlineRemove {
-        line: 1,
+        locator: HeadlineLocator::Line { line: 1 },
         url: url.clone(),
+        version: None,
     }
     .execute(&backend)
     .await
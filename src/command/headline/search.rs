@@ -70,6 +70,7 @@ impl Executable for HeadlineSearch {
 
                     url: url.clone(),
                     line: doc.line_of(headline.start().into()) + 1,
+                    version: doc.version,
                     level: headline.level(),
                     priority: headline.priority().map(|t| t.to_string()),
                     tags: headline.tags().map(|t| t.to_string()).collect(),
@@ -160,6 +161,7 @@ pub struct Result {
     url: Url,
     // zero-based
     line: u32,
+    version: u32,
     level: usize,
     priority: Option<String>,
     tags: Vec<String>,
@@ -11,13 +11,16 @@ use serde::{Deserialize, Serialize};
 
 use crate::{backend::Backend, utils::timestamp::FormatActiveTimestamp};
 
-use crate::command::Executable;
-use crate::utils::headline::find_headline;
+use crate::command::{Executable, VersionConflict};
+use crate::utils::headline::HeadlineLocator;
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct HeadlineUpdate {
     pub url: Url,
-    pub line: u32,
+    #[serde(flatten)]
+    pub locator: HeadlineLocator,
+    #[serde(default)]
+    pub version: Option<u32>,
     pub keyword: Option<String>,
     pub priority: Option<String>,
     pub title: Option<String>,
@@ -33,9 +36,9 @@ impl Executable for HeadlineUpdate {
     type Result = bool;
 
     async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
-        let Some(Some(headline)) = backend
+        let Some((version, headline)) = backend
             .documents()
-            .get_map(&self.url, |doc| find_headline(&doc, self.line))
+            .get_map(&self.url, |doc| (doc.version, self.locator.find(doc)))
         else {
             backend
                 .log_message(
@@ -47,6 +50,19 @@ impl Executable for HeadlineUpdate {
             return Ok(false);
         };
 
+        VersionConflict::check(&self.url, self.version, version)?;
+
+        let Some(headline) = headline else {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("cannot find headline {}", self.locator),
+                )
+                .await;
+
+            return Ok(false);
+        };
+
         let edits = self.edit(headline);
 
         let edits: Vec<_> = edits
@@ -293,7 +309,8 @@ async fn test() {
         fn default() -> Self {
             HeadlineUpdate {
                 url: Url::parse("test://test.org").unwrap(),
-                line: 1,
+                locator: HeadlineLocator::Line { line: 1 },
+                version: None,
                 keyword: None,
                 priority: None,
                 title: None,
@@ -466,7 +483,7 @@ async fn test() {
     HeadlineUpdate {
         title: Some("mon".into()),
         section: Some("section".into()),
-        line: 3,
+        locator: HeadlineLocator::Line { line: 3 },
         keyword: Some("TODO".into()),
         priority: Some("A".into()),
         ..Default::default()
@@ -478,4 +495,28 @@ async fn test() {
         backend.get(&url),
         "* abc\nsection\n** TODO [#A] mon\nsection\n"
     );
+
+    // version
+    let version = backend.documents().get_map(&url, |d| d.version).unwrap();
+    let err = HeadlineUpdate {
+        title: Some("stale".into()),
+        version: Some(version - 1),
+        ..Default::default()
+    }
+    .execute(&backend)
+    .await
+    .unwrap_err();
+    assert!(err.downcast_ref::<VersionConflict>().is_some());
+    HeadlineUpdate {
+        title: Some("fresh".into()),
+        version: Some(version),
+        ..Default::default()
+    }
+    .execute(&backend)
+    .await
+    .unwrap();
+    assert_eq!(
+        backend.get(&url),
+        "* fresh\nsection\n** TODO [#A] mon\nsection\n"
+    );
 }
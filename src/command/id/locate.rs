@@ -0,0 +1,81 @@
+use lsp_types::{MessageType, Url};
+use serde::{Deserialize, Serialize};
+
+use super::load_id_locations;
+use crate::backend::Backend;
+use crate::command::Executable;
+use crate::utils::id::IdLocation;
+
+/// Finds the headline with given `ID` or `CUSTOM_ID` property across all documents,
+/// documents listed in id locations file will be loaded on demand.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct IdLocate {
+    pub id: String,
+}
+
+impl Executable for IdLocate {
+    const NAME: &'static str = "id-locate";
+
+    type Result = Option<IdLocation>;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<Option<IdLocation>> {
+        if let Some(location) = locate(backend, &self.id) {
+            return Ok(Some(location));
+        }
+
+        if let Err(err) = load_id_locations(backend).await {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("failed to load id locations: {err}"),
+                )
+                .await;
+        }
+
+        let Some(url) = backend
+            .documents()
+            .with_ids(|ids| ids.get_url(&self.id).cloned())
+        else {
+            return Ok(None);
+        };
+
+        if backend.documents().get_map(&url, |_| ()).is_none() {
+            let content = backend.read_to_string(&url).await?;
+            backend.documents().insert(url, content);
+        }
+
+        Ok(locate(backend, &self.id))
+    }
+}
+
+fn locate<B: Backend>(backend: &B, id: &str) -> Option<IdLocation> {
+    backend.documents().with_ids(|ids| ids.get(id).cloned())
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test() {
+    use crate::test::TestBackend;
+
+    let backend = TestBackend::default();
+    let url = Url::parse("test://test.org").unwrap();
+
+    backend.documents().insert(
+        url.clone(),
+        "* a\n* b\n:PROPERTIES:\n:ID: b\n:END:\n* c\n:PROPERTIES:\n:CUSTOM_ID: c\n:END:\n",
+    );
+
+    let location = IdLocate { id: "c".into() }
+        .execute(&backend)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(location.url, url);
+    assert_eq!(location.range.start.line, 5);
+
+    assert!(IdLocate { id: "d".into() }
+        .execute(&backend)
+        .await
+        .unwrap()
+        .is_none());
+}
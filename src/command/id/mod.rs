@@ -0,0 +1,38 @@
+mod locate;
+mod update_locations;
+
+pub use locate::*;
+pub use update_locations::*;
+
+use lsp_types::Url;
+use std::collections::HashMap;
+
+use crate::backend::Backend;
+
+/// Reads the id locations file into index, only once
+pub async fn load_id_locations<B: Backend>(backend: &B) -> anyhow::Result<()> {
+    let Some(file) = backend.documents().with_ids(|ids| {
+        if ids.locations_file_loaded {
+            None
+        } else {
+            ids.locations_file_loaded = true;
+            ids.locations_file.clone()
+        }
+    }) else {
+        return Ok(());
+    };
+
+    let content = backend.read_to_string(&file).await?;
+
+    if content.trim().is_empty() {
+        return Ok(());
+    }
+
+    let cached: HashMap<String, Url> = serde_json::from_str(&content)?;
+
+    backend
+        .documents()
+        .with_ids(|ids| ids.extend_cached(cached));
+
+    Ok(())
+}
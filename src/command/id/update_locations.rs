@@ -0,0 +1,46 @@
+use lsp_types::MessageType;
+use serde::{Deserialize, Serialize};
+
+use super::load_id_locations;
+use crate::backend::Backend;
+use crate::command::Executable;
+
+/// Writes ids from all loaded documents to the id locations file,
+/// similar to `org-id-update-id-locations`.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct IdUpdateLocations {}
+
+impl Executable for IdUpdateLocations {
+    const NAME: &'static str = "id-update-locations";
+
+    const TITLE: Option<&'static str> = Some("Update id locations");
+
+    type Result = Option<usize>;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<Option<usize>> {
+        let Some(file) = backend
+            .documents()
+            .with_ids(|ids| ids.locations_file.clone())
+        else {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    "id locations file is not configured".into(),
+                )
+                .await;
+
+            return Ok(None);
+        };
+
+        // make sure we don't lose ids from documents which aren't loaded
+        load_id_locations(backend).await?;
+
+        let cached = backend.documents().with_ids(|ids| ids.to_cached());
+
+        backend
+            .write(&file, &serde_json::to_string_pretty(&cached)?)
+            .await?;
+
+        Ok(Some(cached.len()))
+    }
+}
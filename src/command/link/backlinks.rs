@@ -0,0 +1,30 @@
+use lsp_types::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::command::Executable;
+use crate::utils::link::{collect_backlinks, LinkSource, LinkTarget};
+
+/// Returns links pointing to given headline, or to given file if `line` is omitted
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Backlinks {
+    pub url: Url,
+    /// one-based
+    pub line: Option<u32>,
+}
+
+impl Executable for Backlinks {
+    const NAME: &'static str = "backlinks";
+
+    type Result = Vec<LinkSource>;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<Vec<LinkSource>> {
+        Ok(collect_backlinks(
+            backend,
+            &LinkTarget {
+                url: self.url,
+                line: self.line,
+            },
+        ))
+    }
+}
@@ -0,0 +1,197 @@
+use lsp_types::{Range, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::backend::Backend;
+use crate::command::Executable;
+use crate::utils::headline::find_headline;
+use crate::utils::link::{collect_links, LinkTarget};
+
+/// Emits links between all loaded documents
+#[derive(Deserialize, Serialize, Debug)]
+pub struct LinkGraph {
+    #[serde(default)]
+    pub format: GraphFormat,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum GraphFormat {
+    #[default]
+    Json,
+    Dot,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum GraphResult {
+    Json(Graph),
+    Dot(String),
+}
+
+#[derive(Serialize, Default)]
+pub struct Graph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+/// A file, or a headline if `line` is present
+#[derive(Serialize)]
+struct GraphNode {
+    id: String,
+    url: Url,
+    /// one-based
+    line: Option<u32>,
+    title: String,
+}
+
+#[derive(Serialize)]
+struct GraphEdge {
+    source: String,
+    target: String,
+    url: Url,
+    range: Range,
+}
+
+impl Executable for LinkGraph {
+    const NAME: &'static str = "graph";
+
+    type Result = GraphResult;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<GraphResult> {
+        let mut graph = Graph::default();
+        let mut nodes: HashMap<LinkTarget, String> = HashMap::new();
+
+        let mut node_id = |target: LinkTarget, graph: &mut Graph| -> String {
+            if let Some(id) = nodes.get(&target) {
+                return id.clone();
+            }
+
+            let id = match target.line {
+                Some(line) => format!("{}#{line}", target.url),
+                None => target.url.to_string(),
+            };
+
+            graph.nodes.push(GraphNode {
+                id: id.clone(),
+                title: node_title(backend, &target),
+                url: target.url.clone(),
+                line: target.line,
+            });
+
+            nodes.insert(target, id.clone());
+
+            id
+        };
+
+        for (source, target) in collect_links(backend) {
+            let source_id = node_id(
+                LinkTarget {
+                    url: source.url.clone(),
+                    line: source.headline,
+                },
+                &mut graph,
+            );
+            let target_id = node_id(target, &mut graph);
+
+            graph.edges.push(GraphEdge {
+                source: source_id,
+                target: target_id,
+                url: source.url,
+                range: source.range,
+            });
+        }
+
+        Ok(match self.format {
+            GraphFormat::Json => GraphResult::Json(graph),
+            GraphFormat::Dot => GraphResult::Dot(to_dot(&graph)),
+        })
+    }
+}
+
+fn node_title<B: Backend>(backend: &B, target: &LinkTarget) -> String {
+    let file_name = || {
+        target
+            .url
+            .path_segments()
+            .and_then(|s| s.last())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    match target.line {
+        Some(line) => backend
+            .documents()
+            .get_and_then(&target.url, |doc| {
+                if line as usize > doc.line_numbers() {
+                    return None;
+                }
+                find_headline(doc, line).map(|h| h.title_raw())
+            })
+            .unwrap_or_else(file_name),
+        None => file_name(),
+    }
+}
+
+fn to_dot(graph: &Graph) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+
+    let mut output = "digraph {\n".to_string();
+
+    for node in &graph.nodes {
+        let _ = writeln!(
+            &mut output,
+            "  \"{}\" [label=\"{}\"];",
+            escape(&node.id),
+            escape(&node.title)
+        );
+    }
+
+    for edge in &graph.edges {
+        let _ = writeln!(
+            &mut output,
+            "  \"{}\" -> \"{}\";",
+            escape(&edge.source),
+            escape(&edge.target)
+        );
+    }
+
+    output += "}\n";
+
+    output
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test() {
+    use crate::test::TestBackend;
+
+    let backend = TestBackend::default();
+    let a = Url::parse("test://test.org/a.org").unwrap();
+    let b = Url::parse("test://test.org/b.org").unwrap();
+
+    backend
+        .documents()
+        .insert(a.clone(), "* a\n[[file:./b.org::*b][b]]\n");
+    backend.documents().insert(b.clone(), "* b\n");
+
+    let GraphResult::Dot(dot) = LinkGraph {
+        format: GraphFormat::Dot,
+    }
+    .execute(&backend)
+    .await
+    .unwrap() else {
+        panic!()
+    };
+
+    assert_eq!(
+        dot,
+        r#"digraph {
+  "test://test.org/a.org#1" [label="a"];
+  "test://test.org/b.org#1" [label="b"];
+  "test://test.org/a.org#1" -> "test://test.org/b.org#1";
+}
+"#
+    );
+}
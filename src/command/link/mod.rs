@@ -0,0 +1,5 @@
+mod backlinks;
+mod graph;
+
+pub use backlinks::*;
+pub use graph::*;
@@ -1,12 +1,16 @@
 pub mod clocking;
 pub mod formatting;
 pub mod headline;
+pub mod id;
+pub mod link;
 pub mod src_block;
+pub mod table;
 
 use lsp_types::*;
 use orgize::rowan::ast::AstNode;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
+use std::fmt;
 
 use crate::backend::Backend;
 
@@ -83,6 +87,41 @@ macro_rules! command {
     };
 }
 
+/// Returned when a command expects a different version of the document,
+/// which usually means the document was modified after the client read it.
+#[derive(Serialize, Debug)]
+#[serde(tag = "error", rename = "version-conflict")]
+pub struct VersionConflict {
+    pub url: Url,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl VersionConflict {
+    pub fn check(url: &Url, expected: Option<u32>, actual: u32) -> Result<(), Self> {
+        match expected {
+            Some(expected) if expected != actual => Err(VersionConflict {
+                url: url.clone(),
+                expected,
+                actual,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} has been modified: expected version {}, found {}",
+            self.url, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
 #[derive(Deserialize, Serialize)]
 pub struct SyntaxTree(Url);
 
@@ -115,30 +154,59 @@ impl Executable for PreviewHtml {
 
 pub use clocking::{ClockingStart, ClockingStatus, ClockingStop};
 pub use headline::{
-    HeadlineCreate, HeadlineDuplicate, HeadlineGenerateToc, HeadlineRemove, HeadlineSearch,
-    HeadlineUpdate,
+    HeadlineCreate, HeadlineDuplicate, HeadlineGenerateToc, HeadlineIdGetCreate, HeadlineRemove,
+    HeadlineSearch, HeadlineUpdate,
 };
+pub use id::{IdLocate, IdUpdateLocations};
+pub use link::{Backlinks, LinkGraph};
 pub use src_block::{
-    SrcBlockDetangle, SrcBlockDetangleAll, SrcBlockExecute, SrcBlockExecuteAll, SrcBlockTangle,
+    EffectiveArgs, LibraryFunction, SessionInfo, SrcBlockDetangle, SrcBlockDetangleAll,
+    SrcBlockDetangleFromTarget, SrcBlockEffectiveArgs, SrcBlockExecute, SrcBlockExecuteAll,
+    SrcBlockExecuteCall, SrcBlockLibrary, SrcBlockSessionKill, SrcBlockSessions, SrcBlockTangle,
     SrcBlockTangleAll,
 };
+pub use table::{
+    TableConvertRegion, TableDeleteColumn, TableDeleteRow, TableInsertColumn, TableInsertRow,
+    TableMoveColumn, TableMoveRow, TableRecalculate, TableSort, TableTranspose,
+};
 
 command!(
     PreviewHtml,
     SyntaxTree,
+    Backlinks,
     ClockingStart,
     ClockingStatus,
     ClockingStop,
     HeadlineCreate,
     HeadlineDuplicate,
     HeadlineGenerateToc,
+    HeadlineIdGetCreate,
     HeadlineRemove,
     HeadlineSearch,
     HeadlineUpdate,
+    IdLocate,
+    IdUpdateLocations,
+    LinkGraph,
     SrcBlockDetangle,
     SrcBlockDetangleAll,
+    SrcBlockDetangleFromTarget,
+    SrcBlockEffectiveArgs,
     SrcBlockExecute,
     SrcBlockExecuteAll,
+    SrcBlockExecuteCall,
+    SrcBlockLibrary,
+    SrcBlockSessions,
+    SrcBlockSessionKill,
     SrcBlockTangle,
     SrcBlockTangleAll,
+    TableConvertRegion,
+    TableDeleteColumn,
+    TableDeleteRow,
+    TableInsertColumn,
+    TableInsertRow,
+    TableMoveColumn,
+    TableMoveRow,
+    TableRecalculate,
+    TableSort,
+    TableTranspose,
 );
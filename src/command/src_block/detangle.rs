@@ -7,8 +7,9 @@ use serde::{Deserialize, Serialize};
 use crate::backend::Backend;
 
 use crate::command::Executable;
+use crate::utils::language::language_config;
 use crate::utils::src_block::{
-    collect_src_blocks, header_argument, language_comments, property_drawer, property_keyword,
+    collect_src_blocks, header_argument, property_drawer, property_keyword,
 };
 
 #[derive(Deserialize, Serialize)]
@@ -90,6 +91,174 @@ impl Executable for SrcBlockDetangleAll {
     }
 }
 
+/// Pushes contents of a tangled file back to its source blocks, using comment links
+#[derive(Deserialize, Serialize)]
+pub struct SrcBlockDetangleFromTarget {
+    /// url of the tangled file
+    pub url: Url,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct DetangleReport {
+    pub updated: usize,
+    /// descriptions of comment links which can't be matched to any block
+    pub unmatched: Vec<String>,
+}
+
+impl Executable for SrcBlockDetangleFromTarget {
+    const NAME: &'static str = "src-block-detangle-from-target";
+
+    const TITLE: Option<&'static str> = Some("Detangle from tangled file");
+
+    type Result = DetangleReport;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<DetangleReport> {
+        let content = backend.read_to_string(&self.url).await?;
+
+        let sections = parse_target_sections(backend, &self.url, &content);
+
+        let mut sources: Vec<Url> = vec![];
+        for url in sections.iter().filter_map(|s| s.source.as_ref()) {
+            if !sources.contains(url) {
+                sources.push(url.clone());
+            }
+        }
+
+        // load source org files if needed
+        for url in &sources {
+            if backend.documents().get_map(url, |_| ()).is_some() {
+                continue;
+            }
+
+            match backend.read_to_string(url).await {
+                Ok(text) => backend.documents().insert(url.clone(), text),
+                Err(err) => {
+                    backend
+                        .log_message(MessageType::WARNING, format!("Cannot read {url}: {err}"))
+                        .await
+                }
+            }
+        }
+
+        let mut matched = vec![false; sections.len()];
+        let mut edits = vec![];
+
+        for url in &sources {
+            let Some(options) = backend.documents().get_map(url, |doc| {
+                collect_src_blocks(&doc.org)
+                    .into_iter()
+                    .filter_map(|block| DetangleOptions::new(block, url, backend))
+                    .collect::<Vec<_>>()
+            }) else {
+                continue;
+            };
+
+            for option in options {
+                let Some((begin, _)) = &option.comment_link else {
+                    continue;
+                };
+
+                if option.destination != self.url {
+                    continue;
+                }
+
+                let Some(i) = (0..sections.len()).find(|&i| {
+                    !matched[i]
+                        && sections[i].source.as_ref() == Some(url)
+                        && sections[i].begin == begin.trim()
+                }) else {
+                    continue;
+                };
+
+                matched[i] = true;
+                edits.push((url.clone(), sections[i].content.clone(), option.text_range));
+            }
+        }
+
+        let updated = edits.len();
+
+        backend.apply_edits(edits.into_iter()).await?;
+
+        let unmatched: Vec<_> = sections
+            .into_iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(section, _)| section.description)
+            .collect();
+
+        for description in &unmatched {
+            backend
+                .show_message(
+                    MessageType::WARNING,
+                    format!("Cannot find source block for {description}"),
+                )
+                .await;
+        }
+
+        Ok(DetangleReport { updated, unmatched })
+    }
+}
+
+/// A block of tangled file surrounded by comment links
+struct TargetSection {
+    source: Option<Url>,
+    begin: String,
+    description: String,
+    content: String,
+}
+
+fn parse_target_sections<B: Backend>(
+    backend: &B,
+    target: &Url,
+    content: &str,
+) -> Vec<TargetSection> {
+    let mut sections = vec![];
+    let mut lines = content.lines();
+
+    while let Some(line) = lines.next() {
+        let Some((path, description)) = parse_comment_link(line) else {
+            continue;
+        };
+
+        let end = format!("{description} ends here");
+
+        let mut content = String::new();
+        for line in lines.by_ref() {
+            if line.contains(&end) {
+                break;
+            }
+            content += line;
+            content += "\n";
+        }
+
+        let source = match path.strip_prefix("file:") {
+            Some(path) if !path.starts_with("//") => backend.resolve_in(path, target).ok(),
+            _ => Url::parse(path)
+                .ok()
+                .or_else(|| backend.resolve_in(path, target).ok()),
+        };
+
+        sections.push(TargetSection {
+            source,
+            begin: line.trim().to_string(),
+            description: description.to_string(),
+            content,
+        });
+    }
+
+    sections
+}
+
+/// Parses `[[path::*title][description]]`, returns path and description
+fn parse_comment_link(line: &str) -> Option<(&str, &str)> {
+    let start = line.find("[[")?;
+    let (link, rest) = line[start + 2..].split_once("][")?;
+    let (description, _) = rest.split_once("]]")?;
+    let (path, search) = link.split_once("::")?;
+
+    search.starts_with('*').then_some((path, description))
+}
+
 pub struct DetangleOptions {
     destination: Url,
     comment_link: Option<(String, String)>,
@@ -98,10 +267,10 @@ pub struct DetangleOptions {
 
 impl DetangleOptions {
     pub fn new<B: Backend>(block: SourceBlock, base: &Url, backend: &B) -> Option<Self> {
-        let arg1 = block.parameters().unwrap_or_default();
-        let arg2 = property_drawer(block.syntax()).unwrap_or_default();
-        let arg3 = property_keyword(block.syntax()).unwrap_or_default();
         let language = block.language().unwrap_or_default();
+        let arg1 = block.parameters().unwrap_or_default();
+        let arg2 = property_drawer(block.syntax(), &language);
+        let arg3 = property_keyword(block.syntax(), &language);
 
         let tangle = header_argument(&arg1, &arg2, &arg3, ":tangle", "no");
 
@@ -137,9 +306,11 @@ impl DetangleOptions {
                 .map(|headline| headline.title_raw())
                 .unwrap_or_else(|| "No heading".to_string());
 
-            if let Some((l, r)) = language_comments(&language) {
+            let config = language_config(backend.documents(), &language, block.syntax());
+
+            if let Some((l, r)) = config.comments() {
                 comment_link = Some((
-                    format!("{l} [[{}::*{title}][{title}:{nth}]] {r}", destination),
+                    format!("{l} [[{base}::*{title}][{title}:{nth}]] {r}"),
                     format!("{l} {title}:{nth} ends here {r}"),
                 ));
             }
@@ -177,3 +348,53 @@ impl DetangleOptions {
         }
     }
 }
+
+#[cfg(test)]
+#[tokio::test]
+async fn test() {
+    use crate::test::TestBackend;
+
+    let backend = TestBackend::default();
+    let org = Url::parse("test://test.org/a.org").unwrap();
+    let target = Url::parse("test://test.org/a.js").unwrap();
+
+    backend.documents().insert(
+        org.clone(),
+        r#"* hello
+#+begin_src js :tangle ./a.js :comments link
+console.log(1)
+#+end_src
+"#,
+    );
+    backend.documents().insert(
+        target.clone(),
+        r#"// [[test://test.org/a.org::*hello][hello:1]]
+console.log(2)
+// hello:1 ends here
+// [[test://test.org/a.org::*missing][missing:1]]
+x
+// missing:1 ends here
+"#,
+    );
+
+    let report = SrcBlockDetangleFromTarget { url: target }
+        .execute(&backend)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        DetangleReport {
+            updated: 1,
+            unmatched: vec!["missing:1".to_string()]
+        }
+    );
+    assert_eq!(
+        backend.get(&org),
+        r#"* hello
+#+begin_src js :tangle ./a.js :comments link
+console.log(2)
+#+end_src
+"#
+    );
+}
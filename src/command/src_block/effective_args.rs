@@ -0,0 +1,113 @@
+use lsp_types::*;
+use orgize::rowan::TextSize;
+use orgize::{ast::SourceBlock, rowan::ast::AstNode};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::command::Executable;
+use crate::utils::src_block::{
+    header_argument, property_drawer, property_keyword, split_header_args,
+};
+use crate::utils::var::parse_vars;
+
+/// Header arguments of a source block at each level of inheritance
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct EffectiveArgs {
+    pub language: String,
+    /// `#+PROPERTY: header-args` and `header-args:{language}`
+    pub keyword: String,
+    /// inherited from property drawers of ancestor headlines
+    pub drawer: String,
+    /// parameters of the block itself
+    pub block: String,
+    /// resolved value of each header argument above, arguments not listed
+    /// here use built-in defaults
+    pub args: Vec<(String, String)>,
+    /// merged `:var` bindings
+    pub vars: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SrcBlockEffectiveArgs {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub block_offset: TextSize,
+}
+
+impl Executable for SrcBlockEffectiveArgs {
+    const NAME: &'static str = "src-block-effective-args";
+
+    type Result = Option<EffectiveArgs>;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<Option<EffectiveArgs>> {
+        Ok(backend.documents().get_and_then(&self.url, |doc| {
+            let block = doc.org.node_at_offset::<SourceBlock>(self.block_offset)?;
+            Some(effective_args(&block))
+        }))
+    }
+}
+
+pub fn effective_args(block: &SourceBlock) -> EffectiveArgs {
+    let language = block.language().unwrap_or_default().to_string();
+    let block_args = block.parameters().unwrap_or_default().trim().to_string();
+    let drawer = property_drawer(block.syntax(), &language);
+    let keyword = property_keyword(block.syntax(), &language);
+
+    let mut args: Vec<(String, String)> = vec![];
+
+    for key in [&keyword, &drawer, &block_args]
+        .into_iter()
+        .flat_map(|args| split_header_args(args))
+        .map(|(key, _)| key)
+        .filter(|key| key.len() > 1 && *key != ":var")
+    {
+        if args.iter().all(|(k, _)| k != key) {
+            let value = header_argument(&block_args, &drawer, &keyword, key, "");
+            args.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    args.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let vars = parse_vars(&[&keyword, &drawer, &block_args])
+        .into_iter()
+        .map(|(name, var)| format!("{name}={var}"))
+        .collect();
+
+    EffectiveArgs {
+        language,
+        keyword,
+        drawer,
+        block: block_args,
+        args,
+        vars,
+    }
+}
+
+#[test]
+fn test() {
+    use orgize::Org;
+
+    let org = Org::parse(
+        r#"#+PROPERTY: header-args :results output :var x=1
+* a
+:PROPERTIES:
+:header-args:sh+: :dir /tmp :var x=2
+:END:
+#+begin_src sh :results value :var y="a :b"
+#+end_src
+"#,
+    );
+
+    let args = effective_args(&org.first_node::<SourceBlock>().unwrap());
+
+    assert_eq!(args.drawer, ":dir /tmp :var x=2");
+    assert_eq!(
+        args.args,
+        vec![
+            (":dir".into(), "/tmp".into()),
+            (":results".into(), "value".into())
+        ]
+    );
+    assert_eq!(args.vars, vec!["x=2", r#"y="a :b""#]);
+}
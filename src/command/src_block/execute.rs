@@ -1,15 +1,24 @@
 use lsp_types::*;
 use orgize::rowan::TextSize;
-use orgize::{ast::AffiliatedKeyword, rowan::TextRange, SyntaxKind};
 use orgize::{ast::SourceBlock, rowan::ast::AstNode};
+use orgize::{rowan::TextRange, SyntaxKind, SyntaxNode};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::iter::once;
 
 use crate::command::Executable;
+use crate::utils::call::{
+    collect_invocations, find_named_block, inline_results_range, Call, Invocation, InvocationKind,
+};
+use crate::utils::eval::{confirm, EvalPolicy};
+use crate::utils::language::{language_config, split_args, ExecuteOutput, Executor};
+use crate::utils::library::load_library;
+use crate::utils::noweb::{Evaluation, Noweb, NowebBlock, NowebContext};
 use crate::utils::src_block::{
-    collect_src_blocks, header_argument, language_execute_command, property_drawer,
-    property_keyword,
+    collect_src_blocks, header_argument, property_drawer, property_keyword,
 };
+use crate::utils::table::split_separated;
+use crate::utils::var::{parse_assignments, Var};
 
 use crate::backend::Backend;
 
@@ -28,24 +37,90 @@ impl Executable for SrcBlockExecute {
     type Result = bool;
 
     async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
-        let Some(block) = backend
-            .documents()
-            .get_and_then(&self.url, |doc| doc.org.node_at_offset(self.block_offset))
-        else {
+        load_library(backend).await;
+
+        let Some((block, mut noweb)) = backend.documents().get_and_then(&self.url, |doc| {
+            Some((
+                doc.org.node_at_offset(self.block_offset)?,
+                Noweb::new(&self.url, &doc.org, backend.documents()),
+            ))
+        }) else {
             return Ok(false);
         };
 
-        let Some(options) = ExecuteOptions::new(block) else {
+        noweb.ensure_trusted(backend).await?;
+
+        let Some(options) = ExecuteOptions::new(block, &self.url, backend) else {
             backend
                 .log_message(MessageType::ERROR, "Code block can't be executed.".into())
                 .await;
             return Ok(false);
         };
 
-        let new_text = options.run(backend).await?;
+        let edits = options.run(backend, &mut noweb, false).await?;
 
         backend
-            .apply_edit(self.url, new_text, options.range)
+            .apply_edits(
+                edits
+                    .into_iter()
+                    .map(|(new_text, range)| (self.url.clone(), new_text, range)),
+            )
+            .await?;
+
+        Ok(true)
+    }
+}
+
+/// Evaluates `#+CALL:` line, inline call or inline source at given offset
+#[derive(Serialize, Deserialize)]
+pub struct SrcBlockExecuteCall {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+}
+
+impl Executable for SrcBlockExecuteCall {
+    const NAME: &'static str = "src-block-execute-call";
+
+    const TITLE: Option<&'static str> = Some("Execute");
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        load_library(backend).await;
+
+        let Some((options, mut noweb)) = backend.documents().get_and_then(&self.url, |doc| {
+            let document = doc.org.document().syntax().clone();
+
+            let invocation = collect_invocations(&document)
+                .into_iter()
+                .find(|i| i.range.contains_inclusive(self.offset))?;
+
+            Some((
+                ExecuteOptions::from_invocation(&document, &invocation, &self.url, backend),
+                Noweb::new(&self.url, &doc.org, backend.documents()),
+            ))
+        }) else {
+            return Ok(false);
+        };
+
+        noweb.ensure_trusted(backend).await?;
+
+        let Some(options) = options else {
+            backend
+                .log_message(MessageType::ERROR, "Call can't be executed.".into())
+                .await;
+            return Ok(false);
+        };
+
+        let edits = options.run(backend, &mut noweb, false).await?;
+
+        backend
+            .apply_edits(
+                edits
+                    .into_iter()
+                    .map(|(new_text, range)| (self.url.clone(), new_text, range)),
+            )
             .await?;
 
         Ok(true)
@@ -55,6 +130,9 @@ impl Executable for SrcBlockExecute {
 #[derive(Serialize, Deserialize)]
 pub struct SrcBlockExecuteAll {
     pub url: Url,
+    /// execute blocks even if their results are cached
+    #[serde(default)]
+    pub force: bool,
 }
 
 impl Executable for SrcBlockExecuteAll {
@@ -65,20 +143,53 @@ impl Executable for SrcBlockExecuteAll {
     type Result = bool;
 
     async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
-        let Some(blocks) = backend
-            .documents()
-            .get_map(&self.url, |doc| collect_src_blocks(&doc.org))
-        else {
+        load_library(backend).await;
+
+        let Some((options, mut noweb)) = backend.documents().get_map(&self.url, |doc| {
+            let document = doc.org.document().syntax().clone();
+
+            let mut options: Vec<_> = collect_src_blocks(&doc.org)
+                .into_iter()
+                .filter_map(|block| {
+                    let start = block.start();
+                    Some((start, ExecuteOptions::new(block, &self.url, backend)?))
+                })
+                .chain(
+                    collect_invocations(&document)
+                        .into_iter()
+                        .filter_map(|invocation| {
+                            let options = ExecuteOptions::from_invocation(
+                                &document,
+                                &invocation,
+                                &self.url,
+                                backend,
+                            )?;
+                            Some((invocation.range.start(), options))
+                        }),
+                )
+                .collect();
+
+            options.sort_by_key(|(start, _)| *start);
+
+            (
+                options
+                    .into_iter()
+                    .map(|(_, options)| options)
+                    .collect::<Vec<_>>(),
+                Noweb::new(&self.url, &doc.org, backend.documents()),
+            )
+        }) else {
             return Ok(false);
         };
 
-        let options: Vec<_> = blocks.into_iter().filter_map(ExecuteOptions::new).collect();
+        noweb.ensure_trusted(backend).await?;
 
         let mut edits = Vec::with_capacity(options.len());
 
         for option in options {
-            let content = option.run(backend).await?;
-            edits.push((self.url.clone(), content, option.range));
+            for (content, range) in option.run(backend, &mut noweb, self.force).await? {
+                edits.push((self.url.clone(), content, range));
+            }
         }
 
         backend.apply_edits(edits.into_iter()).await?;
@@ -89,66 +200,426 @@ impl Executable for SrcBlockExecuteAll {
 
 struct ExecuteOptions {
     format: Format,
-    executable: String,
-    content: String,
-    range: TextRange,
+    collection: Collection,
+    handling: Handling,
+    /// raw `:file` value and its resolved url
+    file: Option<(String, Url)>,
+    executor: Executor,
+    block: NowebBlock,
+    /// `:cache yes`
+    cache: bool,
+    /// `:session name`
+    session: Option<String>,
+    /// `:error-results yes`, writes stderr as results if execution failed
+    error_results: bool,
+    /// `:prologue` and `:epilogue`
+    prologue: String,
+    epilogue: String,
+    /// header arguments and language, included in cache hash
+    args: String,
+    placement: Placement,
+}
+
+/// Where results are written to
+enum Placement {
+    /// `#+RESULTS:` after source block or `#+CALL:` line
+    Block {
+        results: Option<ExistingResults>,
+        /// end of the evaluated element, where new results are inserted
+        end: TextSize,
+    },
+    /// `{{{results(...)}}}` after inline call or inline source
+    Inline {
+        /// range of existing results, or empty range after the inline object
+        range: TextRange,
+    },
+}
+
+struct ExistingResults {
+    /// range of `#+RESULTS:` line, excluding trailing newline
+    keyword: TextRange,
+    /// range of results content
+    content: TextRange,
+    /// text of results content
+    text: String,
+    hash: Option<String>,
+}
+
+/// Outcome of executing a block, before results are formatted
+enum Execution {
+    /// user declined evaluating the block
+    Cancelled,
+    /// hash of existing results matches, contains their text
+    Cached(String),
+    Failed(ExecuteOutput),
+    Finished {
+        output: String,
+        /// hash of header arguments and content, if `:cache yes`
+        hash: Option<String>,
+    },
 }
 
 impl ExecuteOptions {
-    pub fn new(block: SourceBlock) -> Option<Self> {
-        let arg1 = block.parameters().unwrap_or_default();
-        let arg2 = property_drawer(block.syntax()).unwrap_or_default();
-        let arg3 = property_keyword(block.syntax()).unwrap_or_default();
-        let language = block.language().unwrap_or_default();
-        let results = header_argument(&arg1, &arg2, &arg3, ":results", "no");
+    pub fn new<B: Backend>(block: SourceBlock, base: &Url, backend: &B) -> Option<Self> {
+        ExecuteOptions::from_parts(
+            block.syntax(),
+            &block.parameters().unwrap_or_default(),
+            "no",
+            NowebBlock::new(&block, backend.documents()),
+            Placement::Block {
+                results: find_existing_results(block.syntax()),
+                end: block.end(),
+            },
+            base,
+            backend,
+        )
+    }
+
+    /// Creates options for `#+CALL:` line, inline call or inline source,
+    /// `node` is the document containing it
+    pub fn from_invocation<B: Backend>(
+        node: &SyntaxNode,
+        invocation: &Invocation,
+        base: &Url,
+        backend: &B,
+    ) -> Option<Self> {
+        let placement = if invocation.is_inline() {
+            let text = node.to_string();
+            let end = invocation.range.end();
+            Placement::Inline {
+                range: inline_results_range(&text, end).unwrap_or(TextRange::empty(end)),
+            }
+        } else {
+            let element = node
+                .token_at_offset(invocation.range.start())
+                .right_biased()?
+                .parent_ancestors()
+                .find(|n| {
+                    n.parent().is_some_and(|p| {
+                        p.kind() == SyntaxKind::SECTION || p.kind() == SyntaxKind::DOCUMENT
+                    })
+                })?;
+
+            Placement::Block {
+                results: find_existing_results(&element),
+                end: element.text_range().end(),
+            }
+        };
+
+        match &invocation.kind {
+            InvocationKind::CallLine(call) | InvocationKind::InlineCall(call) => {
+                match find_named_block(node, &call.name) {
+                    Some(callee) => {
+                        ExecuteOptions::from_call(call, &callee, placement, base, backend)
+                    }
+                    // fall back to library of babel
+                    None => backend.documents().with_library(|library| {
+                        library
+                            .find(&call.name, |_, callee| {
+                                ExecuteOptions::from_call(call, callee, placement, base, backend)
+                            })
+                            .flatten()
+                    }),
+                }
+            }
+            InvocationKind::InlineSrc(src) => {
+                let element = node
+                    .token_at_offset(invocation.range.start())
+                    .right_biased()?
+                    .parent()?;
+
+                let block = NowebBlock::from_parts(
+                    &element,
+                    None,
+                    &src.language,
+                    &src.parameters,
+                    src.body.clone(),
+                    backend.documents(),
+                );
+
+                ExecuteOptions::from_parts(
+                    &element,
+                    &src.parameters,
+                    "replace",
+                    block,
+                    placement,
+                    base,
+                    backend,
+                )
+            }
+        }
+    }
+
+    fn from_call<B: Backend>(
+        call: &Call,
+        callee: &SourceBlock,
+        placement: Placement,
+        base: &Url,
+        backend: &B,
+    ) -> Option<Self> {
+        // header arguments of call take precedence over the called block
+        let parameters = format!(
+            "{} {} {}",
+            call.end,
+            call.inside,
+            callee.parameters().unwrap_or_default()
+        );
+
+        ExecuteOptions::from_callee(
+            callee,
+            parameters.trim(),
+            parse_assignments(&call.arguments),
+            placement,
+            base,
+            backend,
+        )
+    }
+
+    /// Creates options for evaluating `callee` with `args` overriding its `:var`
+    fn from_callee<B: Backend>(
+        callee: &SourceBlock,
+        parameters: &str,
+        args: Vec<(String, Var)>,
+        placement: Placement,
+        base: &Url,
+        backend: &B,
+    ) -> Option<Self> {
+        let mut block = NowebBlock::new(callee, backend.documents());
+
+        block.override_vars(args);
+
+        ExecuteOptions::from_parts(
+            callee.syntax(),
+            parameters,
+            "replace",
+            block,
+            placement,
+            base,
+            backend,
+        )
+    }
+
+    /// Creates options for a block referenced by `<<name()>>` or
+    /// `:var x=name()`, looked up in the document and then library files
+    fn from_evaluation<B: Backend>(
+        node: &SyntaxNode,
+        name: &str,
+        args: Vec<(String, Var)>,
+        base: &Url,
+        backend: &B,
+    ) -> Option<Self> {
+        let from_callee = |callee: &SourceBlock| {
+            // existing results are used if cached
+            let placement = Placement::Block {
+                results: find_existing_results(callee.syntax()),
+                end: callee.end(),
+            };
+
+            ExecuteOptions::from_callee(
+                callee,
+                &callee.parameters().unwrap_or_default(),
+                args,
+                placement,
+                base,
+                backend,
+            )
+        };
+
+        match find_named_block(node, name) {
+            Some(callee) => from_callee(&callee),
+            None => backend
+                .documents()
+                .with_library(|library| library.find(name, |_, callee| from_callee(callee)))
+                .flatten(),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn from_parts<B: Backend>(
+        node: &SyntaxNode,
+        parameters: &str,
+        default_results: &'static str,
+        block: NowebBlock,
+        placement: Placement,
+        base: &Url,
+        backend: &B,
+    ) -> Option<Self> {
+        let language = block.language.clone();
+        let arg1 = parameters;
+        let arg2 = property_drawer(node, &language);
+        let arg3 = property_keyword(node, &language);
+        let results = header_argument(arg1, &arg2, &arg3, ":results", default_results);
 
         if results == "no" {
             return None;
         }
 
-        let mut segs = results.split(&[' ', '\t']).filter(|x| !x.is_empty());
-
-        let format = match (segs.next(), segs.next()) {
-            (Some("output"), Some("code")) | (Some("code"), None) => Format::Code,
-            (Some("output"), Some("list")) | (Some("list"), None) => Format::List,
-            (Some("output"), Some("scalar"))
-            | (Some("scalar"), None)
-            | (Some("output"), Some("verbatim"))
-            | (Some("verbatim"), None) => Format::Verbatim,
-            (Some("output"), Some("html")) | (Some("html"), None) => Format::Html,
-            (Some("output"), Some("latex")) | (Some("latex"), None) => Format::Latex,
-            (Some("output"), Some("raw")) | (Some("raw"), None) => Format::Raw,
-            _ => return None,
+        if EvalPolicy::parse(header_argument(arg1, &arg2, &arg3, ":eval", "yes"))
+            == EvalPolicy::Never
+        {
+            return None;
+        }
+
+        let file = header_argument(arg1, &arg2, &arg3, ":file", "").trim_matches('"');
+
+        // `output` is the default, since blocks are executed as scripts
+        let mut collection = Collection::Output;
+        let mut handling = Handling::Replace;
+        let mut format = if file.is_empty() {
+            Format::Verbatim
+        } else {
+            Format::File
         };
 
-        let range = find_existing_results(&block).unwrap_or_else(|| {
-            let end = block.end();
-            TextRange::empty(end)
-        });
+        for word in results.split_whitespace() {
+            match word {
+                "value" => collection = Collection::Value,
+                "output" => collection = Collection::Output,
+                "replace" => handling = Handling::Replace,
+                "append" => handling = Handling::Append,
+                "prepend" => handling = Handling::Prepend,
+                "silent" => handling = Handling::Silent,
+                "code" => format = Format::Code,
+                "list" => format = Format::List,
+                "scalar" | "verbatim" => format = Format::Verbatim,
+                "table" | "vector" => format = Format::Table,
+                "file" => format = Format::File,
+                "html" => format = Format::Html,
+                "latex" => format = Format::Latex,
+                "raw" => format = Format::Raw,
+                _ => {}
+            }
+        }
 
-        let Some(executable) = language_execute_command(&language) else {
-            return None;
+        let file = match format {
+            Format::File if file.is_empty() => return None,
+            Format::File => Some((file.to_string(), backend.resolve_in(file, base).ok()?)),
+            _ => None,
+        };
+
+        let cache = header_argument(arg1, &arg2, &arg3, ":cache", "no") == "yes";
+        let session = match header_argument(arg1, &arg2, &arg3, ":session", "none") {
+            "none" => None,
+            "" => Some("default".to_string()),
+            session => Some(session.trim_matches('"').to_string()),
         };
 
+        let mut executor = language_config(backend.documents(), &language, node).executor()?;
+
+        if let Ok(timeout) = header_argument(arg1, &arg2, &arg3, ":timeout", "").parse() {
+            executor.timeout = Some(timeout);
+        }
+
+        let dir = header_argument(arg1, &arg2, &arg3, ":dir", "").trim_matches('"');
+        if !dir.is_empty() {
+            // ensure relative paths are resolved as directory
+            let dir = if dir.ends_with('/') {
+                dir.to_string()
+            } else {
+                format!("{dir}/")
+            };
+            executor.dir = Some(backend.resolve_in(&dir, base).ok()?);
+        }
+
+        executor.cmdline = split_args(header_argument(arg1, &arg2, &arg3, ":cmdline", ""));
+
+        executor.env = split_args(header_argument(arg1, &arg2, &arg3, ":env", ""))
+            .into_iter()
+            .filter_map(|var| {
+                let (key, value) = var.split_once('=')?;
+                Some((key.to_string(), value.to_string()))
+            })
+            .collect();
+
+        let text = |key: &str| {
+            let value = header_argument(arg1, &arg2, &arg3, key, "");
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            value.replace("\\n", "\n").replace("\\\"", "\"")
+        };
+
+        let prologue = text(":prologue");
+        let epilogue = text(":epilogue");
+
+        let error_results = header_argument(arg1, &arg2, &arg3, ":error-results", "no") == "yes";
+
         Some(ExecuteOptions {
-            executable: executable.to_string(),
-            content: block.value(),
+            executor,
+            block,
             format,
-            range,
+            collection,
+            handling,
+            file,
+            cache,
+            session,
+            error_results,
+            prologue,
+            epilogue,
+            args: format!("{language}\n{arg1}\n{arg2}\n{arg3}"),
+            placement,
         })
     }
 
-    pub async fn run<B: Backend>(&self, backend: &B) -> anyhow::Result<String> {
-        backend
-            .log_message(
-                MessageType::INFO,
-                format!("Executing src block with `{}`", self.executable),
-            )
-            .await;
+    /// Returns edits for updating results, which are empty if results are
+    /// silent or cached
+    pub async fn run<B: Backend>(
+        &self,
+        backend: &B,
+        noweb: &mut Noweb,
+        force: bool,
+    ) -> anyhow::Result<Vec<(String, TextRange)>> {
+        evaluate_calls(backend, noweb, &self.block, NowebContext::Eval).await?;
+
+        let (output, hash) = match self.execute(backend, noweb, force).await? {
+            Execution::Cancelled | Execution::Cached(_) => return Ok(vec![]),
+            Execution::Failed(result) => {
+                let status = result
+                    .status
+                    .map(|code| format!("exit code {code}"))
+                    .unwrap_or_else(|| "signal".into());
+
+                backend
+                    .show_message(
+                        MessageType::ERROR,
+                        format!("Src block terminated by {status}: {}", result.stderr.trim()),
+                    )
+                    .await;
+
+                if !self.error_results {
+                    return Ok(vec![]);
+                }
+
+                let output = match self.placement {
+                    Placement::Inline { .. } => result.stderr.clone(),
+                    Placement::Block { .. } => once("#+begin_example")
+                        .chain(result.stderr.lines())
+                        .chain(once("#+end_example"))
+                        .fold(String::new(), |acc, line| acc + line + "\n"),
+                };
+
+                // failed results are never cached
+                return Ok(self.edits(output, None));
+            }
+            Execution::Finished { output, hash } => (output, hash),
+        };
+
+        if self.handling == Handling::Silent {
+            backend.show_message(MessageType::INFO, output).await;
+            return Ok(vec![]);
+        }
 
-        let output = backend.execute(&self.executable, &self.content).await?;
+        if let Placement::Inline { .. } = self.placement {
+            let output = match self.format {
+                Format::Raw => output,
+                _ => format!("={}=", inline(&output)),
+            };
+
+            return Ok(self.edits(output, None));
+        }
 
-        let mut output = match self.format {
+        let output = match self.format {
             Format::Code => once("#+begin_src")
                 .chain(output.lines())
                 .chain(once("#+end_src"))
@@ -167,15 +638,257 @@ impl ExecuteOptions {
             Format::Verbatim => output
                 .lines()
                 .fold(String::new(), |acc, line| acc + ": " + line + "\n"),
+            Format::Table => render_table(&parse_table(&output)),
+            Format::File => {
+                let (path, url) = self.file.as_ref().expect("checked in new");
+                backend.write(url, &output).await?;
+                format!("[[file:{path}]]\n")
+            }
             Format::Raw => output,
         };
 
-        if self.range.start() == self.range.end() {
-            output = format!("\n#+RESULTS:\n{output}\n");
+        Ok(self.edits(output, hash))
+    }
+
+    /// Expands and executes the block, blocks it calls must be evaluated
+    /// beforehand, see `evaluate_calls`
+    async fn execute<B: Backend>(
+        &self,
+        backend: &B,
+        noweb: &Noweb,
+        force: bool,
+    ) -> anyhow::Result<Execution> {
+        if self.block.eval == EvalPolicy::Query {
+            let name = match &self.block.name {
+                Some(name) => format!("{name:?}"),
+                None => format!("{} src block", self.block.language),
+            };
+
+            if !confirm(backend, format!("Evaluate {name}?")).await {
+                return Ok(Execution::Cancelled);
+            }
+        }
+
+        let mut content = noweb.expand(&self.block, NowebContext::Eval)?;
+
+        if !self.prologue.is_empty() {
+            content = format!("{}\n{content}", self.prologue);
+        }
+
+        if !self.epilogue.is_empty() {
+            content = format!("{content}\n{}", self.epilogue);
+        }
+
+        if self.collection == Collection::Value {
+            let Some(wrapped) = wrap_value(&self.block.language, &content) else {
+                anyhow::bail!(
+                    "`:results value` is not supported for {:?}, use `:results output` instead",
+                    self.block.language
+                );
+            };
+            content = wrapped;
+        }
+
+        // inline results have no place for hash
+        let hash = (self.cache && matches!(self.placement, Placement::Block { .. }))
+            .then(|| hex_digest(&format!("{}\n{content}", self.args)));
+
+        if let (
+            Some(hash),
+            Placement::Block {
+                results:
+                    Some(ExistingResults {
+                        hash: Some(old),
+                        text,
+                        ..
+                    }),
+                ..
+            },
+        ) = (&hash, &self.placement)
+        {
+            if !force && hash == old {
+                backend
+                    .log_message(
+                        MessageType::INFO,
+                        "Results are cached, skip executing src block".into(),
+                    )
+                    .await;
+                return Ok(Execution::Cached(text.clone()));
+            }
+        }
+
+        backend
+            .log_message(
+                MessageType::INFO,
+                format!(
+                    "Executing src block with `{}`",
+                    self.executor.command.join(" ")
+                ),
+            )
+            .await;
+
+        let result = match &self.session {
+            Some(session) => {
+                backend
+                    .execute_in_session(session, &self.block.language, &self.executor, &content)
+                    .await?
+            }
+            None => backend.execute(&self.executor, &content).await?,
+        };
+
+        backend
+            .log_message(
+                MessageType::LOG,
+                format!("Src block finished in {:?}", result.duration),
+            )
+            .await;
+
+        if !result.success() {
+            return Ok(Execution::Failed(result));
         }
 
-        Ok(output)
+        if !result.stderr.trim().is_empty() {
+            backend
+                .log_message(MessageType::WARNING, result.stderr.clone())
+                .await;
+        }
+
+        Ok(Execution::Finished {
+            output: result.stdout,
+            hash,
+        })
+    }
+
+    fn edits(&self, output: String, hash: Option<String>) -> Vec<(String, TextRange)> {
+        let header = match &hash {
+            Some(hash) => format!("#+RESULTS[{hash}]:"),
+            None => "#+RESULTS:".to_string(),
+        };
+
+        let (results, end) = match &self.placement {
+            Placement::Inline { range } => {
+                return vec![(format!(" {{{{{{results({})}}}}}}", inline(&output)), *range)]
+            }
+            Placement::Block { results, end } => (results, *end),
+        };
+
+        let Some(results) = results else {
+            return vec![(format!("\n{header}\n{output}\n"), TextRange::empty(end))];
+        };
+
+        let range = match self.handling {
+            Handling::Append => TextRange::empty(results.content.end()),
+            Handling::Prepend => TextRange::empty(results.content.start()),
+            _ => results.content,
+        };
+
+        let mut edits = vec![(output, range)];
+
+        if hash.is_some() || results.hash.is_some() {
+            edits.push((header, results.keyword));
+        }
+
+        edits
+    }
+}
+
+/// Evaluates blocks referenced by `<<name()>>` or `:var x=name()` of given
+/// block, so that it can be expanded
+///
+/// Called blocks are executed with their own header arguments, in the same
+/// way as `SrcBlockExecute`, but their results are not written back.
+pub async fn evaluate_calls<B: Backend>(
+    backend: &B,
+    noweb: &mut Noweb,
+    block: &NowebBlock,
+    ctx: NowebContext,
+) -> anyhow::Result<()> {
+    let calls = noweb.calls(block, ctx)?;
+
+    if calls.is_empty() {
+        return Ok(());
+    }
+
+    noweb.ensure_trusted(backend).await?;
+
+    for Evaluation { key, name, args } in calls {
+        let url = noweb.url().clone();
+
+        let Some(options) = backend.documents().get_and_then(&url, |doc| {
+            let document = doc.org.document().syntax().clone();
+            ExecuteOptions::from_evaluation(&document, &name, args, &url, backend)
+        }) else {
+            anyhow::bail!(
+                "cannot evaluate block {key:?}: not found, disabled by `:eval` or its language is unsupported"
+            );
+        };
+
+        let output = match options.execute(backend, noweb, false).await? {
+            Execution::Cancelled => anyhow::bail!("evaluating block {key:?} was cancelled"),
+            Execution::Cached(text) => results_output(&text),
+            Execution::Failed(result) => {
+                anyhow::bail!("evaluating block {key:?} failed: {}", result.stderr.trim())
+            }
+            Execution::Finished { output, .. } => output,
+        };
+
+        noweb.insert_result(key, output);
+    }
+
+    Ok(())
+}
+
+/// Recovers output from existing results, reverting the formatting of `run`
+fn results_output(text: &str) -> String {
+    let mut lines: Vec<_> = text.lines().collect();
+
+    let is_wrapped = lines.len() >= 2
+        && lines[0]
+            .trim_start()
+            .to_ascii_lowercase()
+            .starts_with("#+begin_")
+        && lines[lines.len() - 1]
+            .trim_start()
+            .to_ascii_lowercase()
+            .starts_with("#+end_");
+
+    if is_wrapped {
+        lines = lines[1..lines.len() - 1].to_vec();
     }
+
+    lines
+        .iter()
+        .filter_map(|line| {
+            let trimmed = line.trim_start();
+
+            if is_wrapped {
+                Some(line.to_string())
+            } else if trimmed.starts_with("|-") {
+                None
+            } else if let Some(row) = trimmed.strip_prefix('|') {
+                let cells: Vec<_> = row
+                    .trim_end()
+                    .trim_end_matches('|')
+                    .split('|')
+                    .map(|cell| cell.trim().replace("\\vert{}", "|"))
+                    .collect();
+                Some(cells.join("\t"))
+            } else if let Some(value) = trimmed.strip_prefix(": ") {
+                Some(value.to_string())
+            } else if trimmed == ":" {
+                Some(String::new())
+            } else if let Some(item) = trimmed.strip_prefix("- ") {
+                Some(item.to_string())
+            } else {
+                Some(line.to_string())
+            }
+        })
+        .fold(String::new(), |acc, line| acc + &line + "\n")
+}
+
+/// Joins output into a single line
+fn inline(output: &str) -> String {
+    output.trim().lines().collect::<Vec<_>>().join(" ")
 }
 
 #[derive(Debug)]
@@ -183,17 +896,138 @@ pub enum Format {
     Code,
     List,
     Verbatim,
+    Table,
+    File,
     Html,
     Latex,
     Raw,
 }
 
-fn find_existing_results(block: &SourceBlock) -> Option<TextRange> {
-    let sibling = block.syntax().next_sibling().filter(|n| {
-        n.children()
-            .filter_map(AffiliatedKeyword::cast)
-            .any(|k| k.key().eq_ignore_ascii_case("results"))
-    })?;
+#[derive(Debug, PartialEq)]
+pub enum Collection {
+    /// stdout of the block
+    Output,
+    /// value returned by the block
+    Value,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Handling {
+    Replace,
+    Append,
+    Prepend,
+    Silent,
+}
+
+/// Wraps block content in a function and prints its return value, `None` if
+/// the language is not supported
+fn wrap_value(language: &str, content: &str) -> Option<String> {
+    match language {
+        // parsed with `ast` instead of being indented into a function, which
+        // would change multi-line strings and misplace `from __future__` imports
+        "py" | "python" => Some(format!(
+            r#"import ast as __orgwise_ast
+
+
+def __orgwise_main(source):
+    module = __orgwise_ast.parse(source)
+    future = [
+        stmt
+        for stmt in module.body
+        if isinstance(stmt, __orgwise_ast.ImportFrom) and stmt.module == "__future__"
+    ]
+    body = [stmt for stmt in module.body if stmt not in future]
+    # value of the last expression is returned as well
+    if body and isinstance(body[-1], __orgwise_ast.Expr):
+        body[-1] = __orgwise_ast.copy_location(__orgwise_ast.Return(body[-1].value), body[-1])
+    main = __orgwise_ast.parse("def main():\n    pass\n").body[0]
+    main.body = body or main.body
+    module.body = future + [main]
+    __orgwise_ast.fix_missing_locations(module)
+    namespace = {{"__name__": "__main__"}}
+    exec(compile(module, "<src block>", "exec", dont_inherit=True), namespace)
+    return namespace["main"]()
+
+
+__orgwise_value = __orgwise_main({source})
+if __orgwise_value is not None:
+    import json
+    print(json.dumps(__orgwise_value) if isinstance(__orgwise_value, (list, tuple, dict)) else __orgwise_value)
+"#,
+            // JSON strings are valid Python string literals
+            source = serde_json::to_string(content).ok()?
+        )),
+        "js" | "javascript" => Some(format!(
+            "const __orgwise_value = (() => {{\n{content}\n}})();\n\
+             if (__orgwise_value !== undefined) \
+             console.log(typeof __orgwise_value === \"object\" ? JSON.stringify(__orgwise_value) : __orgwise_value);\n"
+        )),
+        _ => None,
+    }
+}
+
+/// Parses JSON arrays, tab-separated or comma-separated output into table rows
+fn parse_table(output: &str) -> Vec<Vec<String>> {
+    if let Ok(serde_json::Value::Array(rows)) = serde_json::from_str(output.trim()) {
+        let cell = |value: &serde_json::Value| match value {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Null => String::new(),
+            value => value.to_string(),
+        };
+
+        return if rows.iter().all(|row| row.is_array()) {
+            rows.iter()
+                .filter_map(|row| row.as_array())
+                .map(|row| row.iter().map(cell).collect())
+                .collect()
+        } else {
+            vec![rows.iter().map(cell).collect()]
+        };
+    }
+
+    split_separated(output)
+}
+
+fn render_table(rows: &[Vec<String>]) -> String {
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<_> = row.iter().map(|c| c.replace('|', "\\vert{}")).collect();
+            format!("| {} |\n", cells.join(" | "))
+        })
+        .collect()
+}
+
+fn hex_digest(input: &str) -> String {
+    Sha256::digest(input.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Parses `#+RESULTS:` or `#+RESULTS[hash]:`, returns the hash if any
+fn parse_results_keyword(text: &str) -> Option<Option<String>> {
+    let (key, _) = text.trim().strip_prefix("#+")?.split_once(':')?;
+
+    let (key, hash) = match key.split_once('[') {
+        Some((key, hash)) => (key, hash.strip_suffix(']').map(String::from)),
+        None => (key, None),
+    };
+
+    key.eq_ignore_ascii_case("results").then_some(hash)
+}
+
+fn find_existing_results(element: &SyntaxNode) -> Option<ExistingResults> {
+    let sibling = element.next_sibling()?;
+
+    let (keyword, hash) = sibling
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::AFFILIATED_KEYWORD)
+        .find_map(|n| {
+            let text = n.to_string();
+            let hash = parse_results_keyword(&text)?;
+            let len = TextSize::of(text.trim_end());
+            Some((TextRange::at(n.text_range().start(), len), hash))
+        })?;
 
     let (first, last) = match sibling.kind() {
         SyntaxKind::SOURCE_BLOCK | SyntaxKind::EXPORT_BLOCK => {
@@ -222,7 +1056,7 @@ fn find_existing_results(block: &SourceBlock) -> Option<TextRange> {
 
             (iter.next(), iter.last())
         }
-        SyntaxKind::FIXED_WIDTH => {
+        SyntaxKind::FIXED_WIDTH | SyntaxKind::PARAGRAPH => {
             let mut iter = sibling
                 .children_with_tokens()
                 .skip_while(|n| n.kind() == SyntaxKind::AFFILIATED_KEYWORD)
@@ -237,7 +1071,15 @@ fn find_existing_results(block: &SourceBlock) -> Option<TextRange> {
 
     let end = last.or(first).map(|x| x.text_range().end())?;
 
-    Some(TextRange::new(start, end))
+    let offset = sibling.text_range().start();
+    let text = sibling.to_string()[TextRange::new(start - offset, end - offset)].to_string();
+
+    Some(ExistingResults {
+        keyword,
+        content: TextRange::new(start, end),
+        text,
+        hash,
+    })
 }
 
 #[test]
@@ -260,7 +1102,163 @@ fn test() {
     let block = org.first_node::<SourceBlock>().unwrap();
 
     assert_eq!(
-        find_existing_results(&block).unwrap(),
+        find_existing_results(block.syntax()).unwrap().content,
         TextRange::new(61.into(), 85.into(),),
     );
 }
+
+#[test]
+fn test_table() {
+    use orgize::Org;
+
+    assert_eq!(
+        render_table(&parse_table("[[1, \"a\"], [2, null]]\n")),
+        "| 1 | a |\n| 2 |  |\n"
+    );
+    assert_eq!(
+        render_table(&parse_table("a\tb\nc\td\n")),
+        "| a | b |\n| c | d |\n"
+    );
+    assert_eq!(
+        parse_table("x,\"y, z\"\n"),
+        vec![vec!["x".to_string(), "y, z".to_string()]]
+    );
+
+    let org = Org::parse(
+        r#"#+begin_src js :results value table
+return [[1, 2]];
+#+end_src
+
+#+RESULTS:
+| 1 | 2 |
+| 3 | 4 |
+"#,
+    );
+    let block = org.first_node::<SourceBlock>().unwrap();
+
+    let range = find_existing_results(block.syntax()).unwrap().content;
+    assert_eq!(
+        &org.document().syntax().to_string()[range],
+        "| 1 | 2 |\n| 3 | 4 |\n"
+    );
+}
+
+#[test]
+fn test_cache() {
+    use orgize::Org;
+
+    assert_eq!(parse_results_keyword("#+RESULTS:\n"), Some(None));
+    assert_eq!(
+        parse_results_keyword("#+results[ab12]:"),
+        Some(Some("ab12".into()))
+    );
+    assert_eq!(parse_results_keyword("#+NAME: results"), None);
+
+    let org = Org::parse(
+        r#"#+begin_src bash :cache yes
+echo 1
+#+end_src
+
+#+RESULTS[ab12]:
+: 1
+"#,
+    );
+    let block = org.first_node::<SourceBlock>().unwrap();
+
+    let results = find_existing_results(block.syntax()).unwrap();
+    let text = org.document().syntax().to_string();
+    assert_eq!(results.hash.as_deref(), Some("ab12"));
+    assert_eq!(&text[results.keyword], "#+RESULTS[ab12]:");
+    assert_eq!(&text[results.content], ": 1\n");
+}
+
+#[test]
+fn test_results_output() {
+    assert_eq!(results_output(": 1\n:\n: 2\n"), "1\n\n2\n");
+    assert_eq!(results_output("- a\n- b\n"), "a\nb\n");
+    assert_eq!(
+        results_output("| a | 1 |\n|---+---|\n| b | 2 |\n"),
+        "a\t1\nb\t2\n"
+    );
+    assert_eq!(results_output("#+begin_src\n: x\n#+end_src\n"), ": x\n");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_evaluate_calls() {
+    use crate::test::TestBackend;
+
+    let backend = TestBackend::default();
+    backend.documents().with_trust(|trust| trust.all = true);
+    let url = Url::parse("test://test.org").unwrap();
+
+    backend.documents().insert(
+        url.clone(),
+        r#"#+name: square
+#+begin_src python :var n=1 :results value
+return n * n
+#+end_src
+
+#+begin_src python :var a=square(n=2) :results output
+print(a)
+#+end_src
+"#,
+    );
+
+    backend.push_output("4\n");
+    backend.push_output("4\n");
+
+    let executed = SrcBlockExecute {
+        url: url.clone(),
+        block_offset: 94.into(),
+    }
+    .execute(&backend)
+    .await
+    .unwrap();
+    assert!(executed);
+
+    // called block is evaluated with its own header arguments, and its
+    // content is wrapped for printing the returned value
+    let contents = backend.executed();
+    assert_eq!(contents.len(), 2);
+    assert!(contents[0].contains(r#"__orgwise_main("n = 2\nreturn n * n\n")"#));
+    assert_eq!(contents[1], "a = 4\nprint(a)\n");
+
+    assert!(backend.get(&url).contains("#+end_src\n\n#+RESULTS:\n: 4\n"));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_wrap_value() {
+    use crate::test::TestBackend;
+
+    // source is passed as it is, instead of being indented
+    let wrapped = wrap_value("python", "s = \"\"\"a\n  b\"\"\"\nreturn s\n").unwrap();
+    assert!(wrapped.contains(r#"__orgwise_main("s = \"\"\"a\n  b\"\"\"\nreturn s\n")"#));
+    assert!(wrap_value("sh", "echo 1").is_none());
+
+    let backend = TestBackend::default();
+    backend.documents().with_trust(|trust| trust.all = true);
+    let url = Url::parse("test://test.org").unwrap();
+
+    backend.documents().insert(
+        url.clone(),
+        r#"#+begin_src sh :results value
+echo 1
+#+end_src
+"#,
+    );
+
+    let result = SrcBlockExecute {
+        url: url.clone(),
+        block_offset: 0.into(),
+    }
+    .execute(&backend)
+    .await;
+
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("`:results value` is not supported"));
+    assert!(backend.executed().is_empty());
+}
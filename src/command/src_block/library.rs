@@ -0,0 +1,48 @@
+use lsp_types::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::command::Executable;
+use crate::utils::library::load_library;
+use crate::utils::var::parse_vars;
+
+/// A named source block from library files
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LibraryFunction {
+    pub name: String,
+    pub language: String,
+    pub url: Url,
+    /// `:var` bindings with default values, e.g. `x=2`
+    pub vars: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SrcBlockLibrary {}
+
+impl Executable for SrcBlockLibrary {
+    const NAME: &'static str = "src-block-library";
+
+    type Result = Vec<LibraryFunction>;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<Vec<LibraryFunction>> {
+        load_library(backend).await;
+
+        Ok(backend.documents().with_library(|library| {
+            let mut functions = vec![];
+
+            library.for_each(|url, name, block| {
+                functions.push(LibraryFunction {
+                    name: name.to_string(),
+                    language: block.language().unwrap_or_default().to_string(),
+                    url: url.clone(),
+                    vars: parse_vars(&[&block.parameters().unwrap_or_default()])
+                        .into_iter()
+                        .map(|(name, var)| format!("{name}={var}"))
+                        .collect(),
+                });
+            });
+
+            functions
+        }))
+    }
+}
@@ -1,7 +1,13 @@
 mod detangle;
+mod effective_args;
 mod execute;
+mod library;
+mod session;
 mod tangle;
 
 pub use detangle::*;
+pub use effective_args::*;
 pub use execute::*;
+pub use library::*;
+pub use session::*;
 pub use tangle::*;
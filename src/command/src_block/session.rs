@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::command::Executable;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub name: String,
+    pub language: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SrcBlockSessions {}
+
+impl Executable for SrcBlockSessions {
+    const NAME: &'static str = "src-block-sessions";
+
+    type Result = Vec<SessionInfo>;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<Vec<SessionInfo>> {
+        backend.list_sessions().await
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SrcBlockSessionKill {
+    pub name: String,
+}
+
+impl Executable for SrcBlockSessionKill {
+    const NAME: &'static str = "src-block-session-kill";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        backend.kill_session(&self.name).await
+    }
+}
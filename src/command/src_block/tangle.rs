@@ -1,5 +1,3 @@
-// TODO: :noweb support
-
 use lsp_types::*;
 use memchr::memchr2_iter;
 use orgize::ast::Headline;
@@ -9,11 +7,15 @@ use orgize::{ast::SourceBlock, rowan::ast::AstNode};
 use serde::{Deserialize, Serialize};
 use std::fmt::Write;
 
+use super::evaluate_calls;
 use crate::backend::Backend;
 
 use crate::command::Executable;
+use crate::utils::language::language_config;
+use crate::utils::library::load_library;
+use crate::utils::noweb::{Noweb, NowebBlock, NowebContext};
 use crate::utils::src_block::{
-    collect_src_blocks, header_argument, language_comments, property_drawer, property_keyword,
+    collect_src_blocks, header_argument, property_drawer, property_keyword,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -26,6 +28,9 @@ pub struct SrcBlockTangle {
 #[derive(Serialize, Deserialize)]
 pub struct SrcBlockTangleAll {
     pub url: Url,
+    /// returns file contents instead of writing them
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 impl Executable for SrcBlockTangleAll {
@@ -33,50 +38,102 @@ impl Executable for SrcBlockTangleAll {
 
     const TITLE: Option<&'static str> = Some("Tangle all source blocks");
 
-    type Result = bool;
+    type Result = Vec<TangledFile>;
 
-    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
-        let Some(blocks) = backend
-            .documents()
-            .get_map(&self.url, |doc| collect_src_blocks(&doc.org))
-        else {
-            return Ok(false);
-        };
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<Vec<TangledFile>> {
+        load_library(backend).await;
 
-        let mut i = 0;
+        let Some((blocks, mut noweb)) = backend.documents().get_map(&self.url, |doc| {
+            (
+                collect_src_blocks(&doc.org),
+                Noweb::new(&self.url, &doc.org, backend.documents()),
+            )
+        }) else {
+            return Ok(vec![]);
+        };
 
         let options: Vec<_> = blocks
             .into_iter()
             .filter_map(|block| TangleOptions::new(block, &self.url, backend))
             .collect();
 
-        for option in options {
-            let (_range, _new_text) = option.run(backend).await?;
+        // (file, shebang, content, mkdir, permission), in the order of first appearance
+        let mut files: Vec<(TangledFile, Option<&str>, String, bool, Option<u32>)> = vec![];
+
+        for option in &options {
+            evaluate_calls(backend, &mut noweb, &option.block, NowebContext::Tangle).await?;
+            let block_content = noweb.expand(&option.block, NowebContext::Tangle)?;
+
+            let idx = match files.iter().position(|f| f.0.url == option.destination) {
+                Some(idx) => idx,
+                None => {
+                    files.push((
+                        TangledFile {
+                            url: option.destination.clone(),
+                            blocks: 0,
+                            content: None,
+                        },
+                        None,
+                        String::new(),
+                        false,
+                        None,
+                    ));
+                    files.len() - 1
+                }
+            };
 
-            i += 1;
+            let (file, shebang, content, mkdir, permission) = &mut files[idx];
 
-            // TODO:
-            // results
-            //     .entry(&options.)
-            //     .and_modify(|e| {
-            //         e.1 += &options.content;
-            //     })
-            //     .or_insert((options.permission, options.content, options.mkdir));
+            if shebang.is_none() {
+                *shebang = option.shebang.as_deref();
+            }
+            if permission.is_none() {
+                *permission = option.permission;
+            }
+            *mkdir |= option.mkdir;
+            // blank line between consecutive blocks of the same file
+            if option.padline && file.blocks > 0 {
+                *content += "\n";
+            }
+            *content += &option.org_comments;
+            *content += &option.render(&block_content);
+            file.blocks += 1;
         }
 
-        if i > 0 {
+        for (file, shebang, content, mkdir, permission) in &mut files {
+            let content = match shebang {
+                Some(shebang) => format!("{shebang}\n{content}"),
+                None => std::mem::take(content),
+            };
+
+            if self.dry_run {
+                file.content = Some(content);
+                continue;
+            }
+
+            write_file(backend, &file.url, &content, *mkdir, *permission).await?;
+
             backend
                 .show_message(
                     MessageType::INFO,
-                    format!("Found {} code block from {}", i, self.url),
+                    format!("Write {} code block(s) to {}", file.blocks, file.url),
                 )
                 .await;
         }
 
-        Ok(true)
+        Ok(files.into_iter().map(|f| f.0).collect())
     }
 }
 
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TangledFile {
+    pub url: Url,
+    pub blocks: usize,
+    /// only present in dry run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
 impl Executable for SrcBlockTangle {
     const NAME: &'static str = "src-block-tangle";
 
@@ -85,10 +142,14 @@ impl Executable for SrcBlockTangle {
     type Result = bool;
 
     async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
-        let Some(block) = backend
-            .documents()
-            .get_and_then(&self.url, |doc| doc.org.node_at_offset(self.block_offset))
-        else {
+        load_library(backend).await;
+
+        let Some((block, mut noweb)) = backend.documents().get_and_then(&self.url, |doc| {
+            Some((
+                doc.org.node_at_offset(self.block_offset)?,
+                Noweb::new(&self.url, &doc.org, backend.documents()),
+            ))
+        }) else {
             return Ok(false);
         };
 
@@ -100,17 +161,28 @@ impl Executable for SrcBlockTangle {
             return Ok(false);
         };
 
-        let (range, new_text) = options.run(backend).await?;
+        let (range, new_text) = options.run(backend, &mut noweb).await?;
 
-        let content = backend.read_to_string(&options.destination).await?;
+        // destination file might not exist yet
+        let content = backend
+            .read_to_string(&options.destination)
+            .await
+            .unwrap_or_default();
 
-        if let Some((start, end)) = range {
-            let new_content = format!("{}{}{}", &content[0..start], new_text, &content[end..]);
-            backend.write(&options.destination, &new_content).await?;
+        let new_content = if let Some((start, end)) = range {
+            format!("{}{}{}", &content[0..start], new_text, &content[end..])
         } else {
-            let new_content = format!("{}{}", &content, new_text);
-            backend.write(&options.destination, &new_content).await?;
-        }
+            format!("{}{}", &content, new_text)
+        };
+
+        write_file(
+            backend,
+            &options.destination,
+            &new_content,
+            options.mkdir,
+            options.permission,
+        )
+        .await?;
 
         backend
             .show_message(
@@ -125,9 +197,9 @@ impl Executable for SrcBlockTangle {
 
 struct TangleOptions {
     destination: Url,
-    _permission: Option<u32>,
-    content: String,
-    _mkdir: bool,
+    permission: Option<u32>,
+    block: NowebBlock,
+    mkdir: bool,
 
     padline: bool,
     shebang: Option<String>,
@@ -137,10 +209,10 @@ struct TangleOptions {
 
 impl TangleOptions {
     pub fn new<B: Backend>(block: SourceBlock, base: &Url, backend: &B) -> Option<Self> {
-        let arg1 = block.parameters().unwrap_or_default();
-        let arg2 = property_drawer(block.syntax()).unwrap_or_default();
-        let arg3 = property_keyword(block.syntax()).unwrap_or_default();
         let language = block.language().unwrap_or_default();
+        let arg1 = block.parameters().unwrap_or_default();
+        let arg2 = property_drawer(block.syntax(), &language);
+        let arg3 = property_keyword(block.syntax(), &language);
 
         let tangle = header_argument(&arg1, &arg2, &arg3, ":tangle", "no");
 
@@ -149,14 +221,18 @@ impl TangleOptions {
         }
 
         let comments = header_argument(&arg1, &arg2, &arg3, ":comments", "no");
-        let padline = header_argument(&arg1, &arg2, &arg3, ":padline", "no");
-        let shebang = header_argument(&arg1, &arg2, &arg3, ":shebang", "no");
+        let padline = header_argument(&arg1, &arg2, &arg3, ":padline", "yes");
+        let shebang = header_argument(&arg1, &arg2, &arg3, ":shebang", "no").trim_matches('"');
         let mode = header_argument(
             &arg1,
             &arg2,
             &arg3,
             ":tangle-mode",
-            if shebang == "yea" { "o755" } else { "no" },
+            if shebang != "no" && !shebang.is_empty() {
+                "o755"
+            } else {
+                "no"
+            },
         );
         let is_mkdir = header_argument(&arg1, &arg2, &arg3, ":mkdir", "no");
 
@@ -178,18 +254,13 @@ impl TangleOptions {
 
         let destination = backend.resolve_in(tangle, base).ok()?;
 
-        let mut permission = None;
-        if mode != "no"
-            && mode.len() == 4
-            && mode.starts_with('o')
-            && mode.bytes().skip(1).all(|b| (b'0'..=b'7').contains(&b))
-        {
-            permission = u32::from_str_radix(&mode[1..], 8).ok();
-        }
+        let permission = parse_tangle_mode(mode);
+
+        let config = language_config(backend.documents(), &language, block.syntax());
 
         let mut org_comments = String::new();
         if comments == "org" || comments == "both" {
-            if let Some((begin, end)) = language_comments(&language) {
+            if let Some((begin, end)) = config.comments() {
                 let start = block
                     .syntax()
                     .siblings(Direction::Prev)
@@ -215,12 +286,11 @@ impl TangleOptions {
 
         let mut comment_links = None;
         if comments == "yes" || comments == "link" || comments == "noweb" || comments == "both" {
-            if let Some((begin, end)) = language_comments(&language) {
+            if let Some((begin, end)) = config.comments() {
                 comment_links = Some((
                     format!(
-                        "{begin} [[{destination}::*{title}][{title}:{nth}]] {end}",
+                        "{begin} [[{base}::*{title}][{title}:{nth}]] {end}",
                         title = headline_title.as_deref().unwrap_or("No heading"),
-                        destination = destination,
                     ),
                     format!(
                         "{begin} {title}:{nth} ends here {end}",
@@ -237,10 +307,10 @@ impl TangleOptions {
                 None
             },
             destination,
-            _permission: permission,
+            permission,
             org_comments,
-            content: block.value(),
-            _mkdir: is_mkdir != "no",
+            block: NowebBlock::new(&block, backend.documents()),
+            mkdir: is_mkdir != "no",
             padline: padline != "no",
             comment_links,
         })
@@ -249,8 +319,15 @@ impl TangleOptions {
     pub async fn run<B: Backend>(
         &self,
         backend: &B,
+        noweb: &mut Noweb,
     ) -> anyhow::Result<(Option<(usize, usize)>, String)> {
-        let content = backend.read_to_string(&self.destination).await?;
+        evaluate_calls(backend, noweb, &self.block, NowebContext::Tangle).await?;
+        let block_content = noweb.expand(&self.block, NowebContext::Tangle)?;
+
+        let content = backend
+            .read_to_string(&self.destination)
+            .await
+            .unwrap_or_default();
 
         let mut range = None;
         if let Some((begin, end)) = &self.comment_links {
@@ -298,29 +375,109 @@ impl TangleOptions {
             new_text += "\n";
         }
 
-        if let Some((begin, end)) = &self.comment_links {
-            new_text += &begin;
-            new_text += "\n";
-            new_text += &self.content;
-            if self.padline {
-                new_text += "\n";
-            }
-            new_text += &end;
-            new_text += "\n";
-        } else {
-            new_text += &self.content;
-            if self.padline {
-                new_text += "\n";
-            }
+        new_text += &self.render(&block_content);
+
+        Ok((range, new_text))
+    }
+
+    /// Wraps expanded block content with comment links
+    fn render(&self, block_content: &str) -> String {
+        let mut text = String::new();
+
+        if let Some((begin, _)) = &self.comment_links {
+            text += begin;
+            text += "\n";
         }
 
-        // TODO: set file permission
-        // TODO: run mkdir
+        text += block_content;
 
-        Ok((range, new_text))
+        if let Some((_, end)) = &self.comment_links {
+            text += end;
+            text += "\n";
+        }
+
+        text
     }
 }
 
+/// Writes tangled file, creates its parent directory if `mkdir` is true
+async fn write_file<B: Backend>(
+    backend: &B,
+    url: &Url,
+    content: &str,
+    mkdir: bool,
+    permission: Option<u32>,
+) -> anyhow::Result<()> {
+    if mkdir {
+        let parent = url.join(".")?;
+
+        if let Err(err) = backend.create_dir_all(&parent).await {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Cannot create directory {parent}: {err}"),
+                )
+                .await;
+        }
+    }
+
+    backend.write(url, content).await?;
+
+    if let Some(mode) = permission {
+        if let Err(err) = backend.set_permissions(url, mode).await {
+            backend
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Cannot set permission of {url} to {mode:o}: {err}"),
+                )
+                .await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses `:tangle-mode`, e.g. `(identity #o755)`, `#o755`, `o755` or `rwxr-xr-x`
+fn parse_tangle_mode(mode: &str) -> Option<u32> {
+    let mode = mode.trim();
+
+    let mode = mode
+        .strip_prefix("(identity")
+        .and_then(|s| s.strip_suffix(')'))
+        .map(str::trim)
+        .unwrap_or(mode);
+
+    if let Some(octal) = mode.strip_prefix("#o").or_else(|| mode.strip_prefix('o')) {
+        return u32::from_str_radix(octal, 8).ok().filter(|&m| m <= 0o7777);
+    }
+
+    if mode.len() == 9 && mode.is_ascii() {
+        let mut permission = 0;
+        for (i, b) in mode.bytes().enumerate() {
+            let expected = [b'r', b'w', b'x'][i % 3];
+            permission <<= 1;
+            if b == expected {
+                permission |= 1;
+            } else if b != b'-' {
+                return None;
+            }
+        }
+        return Some(permission);
+    }
+
+    None
+}
+
+#[test]
+fn tangle_mode() {
+    assert_eq!(parse_tangle_mode("(identity #o755)"), Some(0o755));
+    assert_eq!(parse_tangle_mode("#o600"), Some(0o600));
+    assert_eq!(parse_tangle_mode("o744"), Some(0o744));
+    assert_eq!(parse_tangle_mode("rwxr-xr--"), Some(0o754));
+    assert_eq!(parse_tangle_mode("no"), None);
+    assert_eq!(parse_tangle_mode("o8"), None);
+}
+
 #[cfg(test)]
 #[tokio::test]
 async fn test() {
@@ -334,9 +491,33 @@ async fn test() {
         r#"#+begin_src js :tangle ./a.js
 console.log('a')
 #+end_src
+
+#+begin_src js :tangle ./b.js :noweb yes
+function b() {
+  <<log>>
+}
+#+end_src
+
+#+name: log
+#+begin_src js
+console.log('b')
+#+end_src
 "#,
     );
 
+    SrcBlockTangle {
+        url: url.clone(),
+        block_offset: 58.into(),
+    }
+    .execute(&backend)
+    .await
+    .unwrap();
+
+    assert_eq!(
+        backend.get(&Url::parse("test://test.org/b.js").unwrap()),
+        "\nfunction b() {\n  console.log('b')\n}\n"
+    );
+
     SrcBlockTangle {
         url: url.clone(),
         block_offset: 0.into(),
@@ -350,3 +531,95 @@ console.log('a')
         "\nconsole.log('a')\n"
     );
 }
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_tangle_all() {
+    use crate::test::TestBackend;
+
+    let backend = TestBackend::default();
+    let url = Url::parse("test://test.org").unwrap();
+
+    backend.documents().insert(
+        url.clone(),
+        r##"#+begin_src sh :tangle ./a.sh :shebang "#!/bin/bash"
+echo 1
+#+end_src
+
+#+begin_src sh :tangle ./b.sh
+echo 2
+#+end_src
+
+#+begin_src sh :tangle ./a.sh :shebang "#!/bin/bash"
+echo 3
+#+end_src
+
+#+begin_src sh :tangle ./b.sh :padline no
+echo 4
+#+end_src
+"##,
+    );
+
+    let files = SrcBlockTangleAll {
+        url: url.clone(),
+        dry_run: false,
+    }
+    .execute(&backend)
+    .await
+    .unwrap();
+
+    let a = Url::parse("test://test.org/a.sh").unwrap();
+    let b = Url::parse("test://test.org/b.sh").unwrap();
+
+    assert_eq!(
+        files,
+        vec![
+            TangledFile {
+                url: a.clone(),
+                blocks: 2,
+                content: None,
+            },
+            TangledFile {
+                url: b.clone(),
+                blocks: 2,
+                content: None,
+            }
+        ]
+    );
+    assert_eq!(backend.get(&a), "#!/bin/bash\necho 1\n\necho 3\n");
+    assert_eq!(backend.get(&b), "echo 2\necho 4\n");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_tangle_untrusted() {
+    use crate::test::TestBackend;
+
+    let backend = TestBackend::default();
+    let url = Url::parse("test://test.org").unwrap();
+
+    backend.documents().insert(
+        url.clone(),
+        r#"#+begin_src sh :tangle ./a.sh :noweb yes
+<<f()>>
+#+end_src
+
+#+name: f
+#+begin_src sh
+echo 1
+#+end_src
+"#,
+    );
+
+    let result = SrcBlockTangleAll {
+        url: url.clone(),
+        dry_run: true,
+    }
+    .execute(&backend)
+    .await;
+
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("untrusted file test://test.org"));
+}
@@ -0,0 +1,275 @@
+use lsp_types::*;
+use orgize::ast::OrgTable;
+use orgize::rowan::{ast::AstNode, TextRange, TextSize};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::command::Executable;
+use crate::utils::table::{split_separated, SortKind, Table, TableRange, TableRow};
+
+/// Applies `f` to the table at `offset` with the row and column at `offset`,
+/// then replaces the table with realigned result
+async fn edit_table<B: Backend>(
+    backend: &B,
+    url: Url,
+    offset: TextSize,
+    f: impl FnOnce(&mut Table, usize, usize),
+) -> anyhow::Result<bool> {
+    let Some((range, text, original)) = backend.documents().get_and_then(&url, |doc| {
+        let table = doc.org.node_at_offset::<OrgTable>(offset)?;
+        let range = TableRange::new(table.syntax())?;
+        let (row, column) = range.cell_at(offset);
+
+        let mut edited = range.table.clone();
+        f(&mut edited, row, column);
+
+        Some((range.range, range.replacement(&edited), range.original))
+    }) else {
+        return Ok(false);
+    };
+
+    if text == original {
+        return Ok(false);
+    }
+
+    backend.apply_edit(url, text, range).await?;
+
+    Ok(true)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TableInsertRow {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+    /// inserts below current row instead of above
+    #[serde(default)]
+    pub below: bool,
+}
+
+impl Executable for TableInsertRow {
+    const NAME: &'static str = "table-insert-row";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        edit_table(backend, self.url, self.offset, |table, row, _| {
+            table.insert_row(if self.below { row + 1 } else { row })
+        })
+        .await
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TableDeleteRow {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+}
+
+impl Executable for TableDeleteRow {
+    const NAME: &'static str = "table-delete-row";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        edit_table(backend, self.url, self.offset, |table, row, _| {
+            if row < table.rows.len() {
+                table.rows.remove(row);
+            }
+        })
+        .await
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TableInsertColumn {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+    /// inserts right to current column instead of left
+    #[serde(default)]
+    pub right: bool,
+}
+
+impl Executable for TableInsertColumn {
+    const NAME: &'static str = "table-insert-column";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        edit_table(backend, self.url, self.offset, |table, _, column| {
+            table.insert_column(if self.right { column + 1 } else { column })
+        })
+        .await
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TableDeleteColumn {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+}
+
+impl Executable for TableDeleteColumn {
+    const NAME: &'static str = "table-delete-column";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        edit_table(backend, self.url, self.offset, |table, _, column| {
+            table.remove_column(column)
+        })
+        .await
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TableMoveRow {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+    /// moves up instead of down
+    #[serde(default)]
+    pub up: bool,
+}
+
+impl Executable for TableMoveRow {
+    const NAME: &'static str = "table-move-row";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        edit_table(backend, self.url, self.offset, |table, row, _| {
+            let other = if self.up {
+                row.checked_sub(1)
+            } else {
+                Some(row + 1)
+            };
+
+            if let Some(other) = other.filter(|other| *other < table.rows.len()) {
+                table.rows.swap(row, other);
+            }
+        })
+        .await
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TableMoveColumn {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+    /// moves left instead of right
+    #[serde(default)]
+    pub left: bool,
+}
+
+impl Executable for TableMoveColumn {
+    const NAME: &'static str = "table-move-column";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        edit_table(backend, self.url, self.offset, |table, _, column| {
+            let other = if self.left {
+                column.checked_sub(1)
+            } else {
+                Some(column + 1)
+            };
+
+            if let Some(other) = other {
+                table.swap_columns(column, other);
+            }
+        })
+        .await
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TableSort {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+    pub kind: SortKind,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+impl Executable for TableSort {
+    const NAME: &'static str = "table-sort";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        edit_table(backend, self.url, self.offset, |table, row, column| {
+            if table.rows.get(row) != Some(&TableRow::Rule) {
+                table.sort(row, column, self.kind, self.descending);
+            }
+        })
+        .await
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TableTranspose {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+}
+
+impl Executable for TableTranspose {
+    const NAME: &'static str = "table-transpose";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        edit_table(backend, self.url, self.offset, |table, _, _| {
+            table.transpose()
+        })
+        .await
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TableConvertRegion {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub start: TextSize,
+    #[serde(with = "crate::utils::text_size")]
+    pub end: TextSize,
+}
+
+impl Executable for TableConvertRegion {
+    const NAME: &'static str = "table-convert-region";
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        let range = TextRange::new(self.start.min(self.end), self.start.max(self.end));
+
+        let Some(text) = backend.documents().get_and_then(&self.url, |doc| {
+            let region = doc
+                .text
+                .get(usize::from(range.start())..usize::from(range.end()))?;
+
+            let table = Table::from_cells(split_separated(region));
+            if table.rows.is_empty() {
+                return None;
+            }
+
+            let mut text = table.render();
+            if !region.ends_with('\n') {
+                text.pop();
+            }
+            Some(text)
+        }) else {
+            return Ok(false);
+        };
+
+        backend.apply_edit(self.url, text, range).await?;
+
+        Ok(true)
+    }
+}
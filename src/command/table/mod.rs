@@ -0,0 +1,5 @@
+mod edit;
+mod recalculate;
+
+pub use edit::*;
+pub use recalculate::*;
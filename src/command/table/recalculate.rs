@@ -0,0 +1,63 @@
+use lsp_types::*;
+use orgize::ast::OrgTable;
+use orgize::rowan::{ast::AstNode, TextSize};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::command::Executable;
+use crate::utils::table::TableRange;
+use crate::utils::tblfm::{recalculate, tblfm_lines};
+
+#[derive(Deserialize, Serialize)]
+pub struct TableRecalculate {
+    pub url: Url,
+    #[serde(with = "crate::utils::text_size")]
+    pub offset: TextSize,
+}
+
+impl Executable for TableRecalculate {
+    const NAME: &'static str = "table-recalculate";
+
+    const TITLE: Option<&'static str> = Some("Recalculate");
+
+    type Result = bool;
+
+    async fn execute<B: Backend>(self, backend: &B) -> anyhow::Result<bool> {
+        let Some((range, text, original, errors)) =
+            backend.documents().get_and_then(&self.url, |doc| {
+                let table = doc.org.node_at_offset::<OrgTable>(self.offset)?;
+                let range = TableRange::new(table.syntax())?;
+
+                let mut recalculated = range.table.clone();
+                let errors = recalculate(&mut recalculated, &tblfm_lines(table.syntax()));
+
+                Some((
+                    range.range,
+                    range.replacement(&recalculated),
+                    range.original,
+                    errors,
+                ))
+            })
+        else {
+            return Ok(false);
+        };
+
+        if !errors.is_empty() {
+            let errors: Vec<_> = errors.iter().map(|err| format!("{err:#}")).collect();
+            backend
+                .show_message(
+                    MessageType::WARNING,
+                    format!("Skipped formulas: {}", errors.join("; ")),
+                )
+                .await;
+        }
+
+        if text == original {
+            return Ok(false);
+        }
+
+        backend.apply_edit(self.url, text, range).await?;
+
+        Ok(true)
+    }
+}
@@ -5,9 +5,14 @@ use orgize::{
 };
 
 use crate::command::{
-    ClockingStop, HeadlineGenerateToc, SrcBlockDetangle, SrcBlockExecute, SrcBlockTangle,
+    ClockingStop, HeadlineGenerateToc, SrcBlockDetangle, SrcBlockExecute, SrcBlockExecuteCall,
+    SrcBlockTangle, TableRecalculate,
 };
+use crate::utils::call::{collect_invocations, InvocationKind};
+use crate::utils::eval::EvalPolicy;
+use crate::utils::headline::HeadlineLocator;
 use crate::utils::src_block::{header_argument, property_drawer, property_keyword};
+use crate::utils::tblfm::tblfm_lines;
 use crate::{backend::Backend, command::ClockingStart};
 use crate::{backend::OrgDocument, utils::clocking::find_logbook};
 
@@ -23,6 +28,23 @@ pub fn code_lens<B: Backend>(backend: &B, params: CodeLensParams) -> Option<Vec<
 
             doc.traverse(&mut traverser);
 
+            for invocation in collect_invocations(doc.org.document().syntax()) {
+                if let InvocationKind::CallLine(_) = invocation.kind {
+                    let start = invocation.range.start();
+                    traverser.lens.push(CodeLens {
+                        range: doc.range_of2(start, start),
+                        command: Some(
+                            SrcBlockExecuteCall {
+                                offset: start,
+                                url: traverser.url.clone(),
+                            }
+                            .into(),
+                        ),
+                        data: None,
+                    });
+                }
+            }
+
             traverser.lens
         })
 }
@@ -43,15 +65,20 @@ impl<'a> Traverser for CodeLensTraverser<'a> {
             Event::Enter(Container::SourceBlock(block)) => {
                 let start = block.start();
 
+                let language = block.language().unwrap_or_default();
                 let arg1 = block.parameters().unwrap_or_default();
-                let arg2 = property_drawer(block.syntax()).unwrap_or_default();
-                let arg3 = property_keyword(block.syntax()).unwrap_or_default();
+                let arg2 = property_drawer(block.syntax(), &language);
+                let arg3 = property_keyword(block.syntax(), &language);
 
                 let range = self.doc.range_of2(start, start);
 
                 let tangle = header_argument(&arg1, &arg2, &arg3, ":tangle", "no");
 
-                if header_argument(&arg1, &arg2, &arg3, ":results", "no") != "no" {
+                let eval = EvalPolicy::parse(header_argument(&arg1, &arg2, &arg3, ":eval", "yes"));
+
+                if header_argument(&arg1, &arg2, &arg3, ":results", "no") != "no"
+                    && eval != EvalPolicy::Never
+                {
                     self.lens.push(CodeLens {
                         range,
                         command: Some(
@@ -93,6 +120,25 @@ impl<'a> Traverser for CodeLensTraverser<'a> {
 
                 ctx.skip();
             }
+            Event::Enter(Container::OrgTable(table)) => {
+                if !tblfm_lines(table.syntax()).is_empty() {
+                    let start = table.syntax().text_range().start();
+
+                    self.lens.push(CodeLens {
+                        range: self.doc.range_of2(start, start),
+                        command: Some(
+                            TableRecalculate {
+                                offset: start,
+                                url: self.url.clone(),
+                            }
+                            .into(),
+                        ),
+                        data: None,
+                    });
+                }
+
+                ctx.skip();
+            }
             Event::Enter(Container::Headline(headline)) => {
                 let start = headline.start();
 
@@ -116,13 +162,19 @@ impl<'a> Traverser for CodeLensTraverser<'a> {
                         command: Some(if headline.clocks().any(|c| c.is_running()) {
                             ClockingStop {
                                 url: self.url.clone(),
-                                line: self.doc.line_of(start.into()) + 1,
+                                locator: HeadlineLocator::Line {
+                                    line: self.doc.line_of(start.into()) + 1,
+                                },
+                                version: Some(self.doc.version),
                             }
                             .into()
                         } else {
                             ClockingStart {
                                 url: self.url.clone(),
-                                line: self.doc.line_of(start.into()) + 1,
+                                locator: HeadlineLocator::Line {
+                                    line: self.doc.line_of(start.into()) + 1,
+                                },
+                                version: Some(self.doc.version),
                             }
                             .into()
                         }),
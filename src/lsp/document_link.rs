@@ -64,6 +64,16 @@ fn resolve<B: Backend>(backend: &B, data: Value) -> Option<Url> {
                 url
             })
         }
+        ("id", _, id) => backend.documents().with_ids(|ids| {
+            if let Some(location) = ids.get(&id) {
+                let mut url = location.url.clone();
+                // range is zero-based
+                url.set_fragment(Some(&(location.range.start.line + 1).to_string()));
+                Some(url)
+            } else {
+                ids.get_url(&id).cloned()
+            }
+        }),
         ("resolve", base, path) => backend.resolve_in(&path, &base).ok(),
         _ => None,
     }
@@ -118,6 +128,8 @@ impl<'a> DocumentLinkTraverser<'a> {
             )
         } else if path_str.starts_with("http://") || path_str.starts_with("https://") {
             (Some(Url::parse(path_str).ok()?), None)
+        } else if let Some(id) = path_str.strip_prefix("id:") {
+            (None, serde_json::to_value(("id", &self.base, id)).ok())
         } else if let Some(id) = path_str.strip_prefix('#') {
             (
                 None,
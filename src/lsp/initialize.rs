@@ -1,10 +1,12 @@
 use lsp_types::*;
 use orgize::ParseConfig;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 use super::semantic_token;
 use crate::backend::Backend;
 use crate::command::OrgwiseCommand;
+use crate::utils::language::LanguageConfig;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -13,6 +15,21 @@ pub struct InitializationOptions {
     pub todo_keywords: Vec<String>,
     #[serde(default)]
     pub done_keywords: Vec<String>,
+    /// file for persisting id locations, e.g. `~/.orgwise-id-locations.json`
+    #[serde(default)]
+    pub id_locations_file: Option<String>,
+    /// language settings, overriding ones from `orgwise.toml`
+    #[serde(default)]
+    pub languages: HashMap<String, LanguageConfig>,
+    /// directories allowed to execute source blocks without confirmation
+    #[serde(default)]
+    pub trusted_directories: Vec<String>,
+    /// org files whose named source blocks are callable from any document
+    #[serde(default)]
+    pub library_files: Vec<String>,
+    /// seconds before execution is killed, unless set for the language
+    #[serde(default)]
+    pub timeout: Option<u64>,
 }
 
 pub async fn initialize<B: Backend>(backend: &B, params: InitializeParams) -> InitializeResult {
@@ -30,6 +47,17 @@ pub async fn initialize<B: Backend>(backend: &B, params: InitializeParams) -> In
             )
             .await;
 
+        set_id_locations_file(backend, initialization_options.id_locations_file.as_deref());
+        set_languages(backend, initialization_options.languages);
+        set_trusted_directories(backend, initialization_options.trusted_directories);
+        set_library_files(backend, initialization_options.library_files);
+
+        if initialization_options.timeout.is_some() {
+            backend
+                .documents()
+                .set_default_timeout(initialization_options.timeout);
+        }
+
         backend.documents().set_default_parse_config(ParseConfig {
             todo_keywords: (
                 initialization_options.todo_keywords,
@@ -43,8 +71,13 @@ pub async fn initialize<B: Backend>(backend: &B, params: InitializeParams) -> In
         server_info: None,
         offset_encoding: None,
         capabilities: ServerCapabilities {
-            text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                TextDocumentSyncKind::INCREMENTAL,
+            text_document_sync: Some(TextDocumentSyncCapability::Options(
+                TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(TextDocumentSyncKind::INCREMENTAL),
+                    save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                    ..Default::default()
+                },
             )),
             execute_command_provider: Some(ExecuteCommandOptions {
                 commands: OrgwiseCommand::all(),
@@ -104,3 +137,38 @@ pub async fn initialize<B: Backend>(backend: &B, params: InitializeParams) -> In
         },
     }
 }
+
+pub fn set_id_locations_file<B: Backend>(backend: &B, path: Option<&str>) {
+    let file = path.and_then(|path| match backend.home_dir() {
+        Some(home_dir) => backend.resolve_in(path, &home_dir).ok(),
+        None => Url::parse(path).ok(),
+    });
+
+    backend.documents().with_ids(|ids| {
+        ids.locations_file = file;
+        ids.locations_file_loaded = false;
+    });
+}
+
+pub fn set_languages<B: Backend>(backend: &B, config: HashMap<String, LanguageConfig>) {
+    backend.documents().with_languages(|languages| {
+        for (name, config) in config {
+            let existing = languages.remove(&name).unwrap_or_default();
+            languages.insert(name, existing.merge(config));
+        }
+    });
+}
+
+pub fn set_trusted_directories<B: Backend>(backend: &B, directories: Vec<String>) {
+    backend
+        .documents()
+        .with_trust(|trust| trust.directories.extend(directories));
+}
+
+pub fn set_library_files<B: Backend>(backend: &B, files: Vec<String>) {
+    backend.documents().with_library(|library| {
+        library.files.extend(files);
+        // reload on next use
+        library.loaded = false;
+    });
+}
@@ -21,12 +21,22 @@ pub use references::*;
 pub use semantic_token::*;
 
 use crate::backend::Backend;
+use crate::command::id::load_id_locations;
 use lsp_types::*;
 
 pub async fn initialized<B: Backend>(backend: &B) {
     backend
         .log_message(MessageType::WARNING, "Initialized".into())
         .await;
+
+    if let Err(err) = load_id_locations(backend).await {
+        backend
+            .log_message(
+                MessageType::WARNING,
+                format!("Failed to load id locations: {err}"),
+            )
+            .await;
+    }
 }
 
 pub fn did_change_configuration<B: Backend>(_: &B, _: DidChangeConfigurationParams) {}
@@ -37,6 +47,22 @@ pub fn did_open<B: Backend>(backend: &B, params: DidOpenTextDocumentParams) {
         .insert(params.text_document.uri, params.text_document.text);
 }
 
+/// Library files are reloaded after being saved
+pub fn did_save<B: Backend>(backend: &B, params: DidSaveTextDocumentParams) {
+    backend
+        .documents()
+        .with_library(|library| library.invalidate(&params.text_document.uri));
+}
+
+/// Library files are reloaded after being modified by other programs
+pub fn did_change_watched_files<B: Backend>(backend: &B, params: DidChangeWatchedFilesParams) {
+    backend.documents().with_library(|library| {
+        for change in params.changes {
+            library.invalidate(&change.uri);
+        }
+    });
+}
+
 pub fn did_change<B: Backend>(backend: &B, params: DidChangeTextDocumentParams) {
     for change in params.content_changes {
         backend
@@ -1,5 +1,6 @@
 use lsp_types::*;
 use orgize::{
+    ast::Headline,
     export::{Container, Event, TraversalContext, Traverser},
     rowan::{ast::AstNode, TextRange, TokenAtOffset},
     Org, SyntaxKind, SyntaxToken,
@@ -7,15 +8,53 @@ use orgize::{
 
 use crate::backend::Backend;
 use crate::backend::OrgDocument;
+use crate::utils::link::{collect_backlinks, LinkTarget};
 
 pub fn references<B: Backend>(backend: &B, params: ReferenceParams) -> Option<Vec<Location>> {
-    let symbol = backend.documents().get_and_then(
-        &params.text_document_position.text_document.uri,
-        |doc| {
-            let offset = doc.offset_of(params.text_document_position.position);
-            locate_symbol(&doc.org, offset)
-        },
-    )?;
+    let uri = &params.text_document_position.text_document.uri;
+
+    let headline = backend.documents().get_and_then(uri, |doc| {
+        let offset = doc.offset_of(params.text_document_position.position);
+        let headline = locate_headline(&doc.org, offset)?;
+        Some((
+            doc.line_of(headline.start().into()) + 1,
+            doc.range_of(headline.syntax().text_range()),
+        ))
+    });
+
+    // cursor on headline title: find links pointing to this headline
+    if let Some((line, range)) = headline {
+        let mut locations: Vec<_> = collect_backlinks(
+            backend,
+            &LinkTarget {
+                url: uri.clone(),
+                line: Some(line),
+            },
+        )
+        .into_iter()
+        .map(|source| Location {
+            uri: source.url,
+            range: source.range,
+        })
+        .collect();
+
+        if params.context.include_declaration {
+            locations.insert(
+                0,
+                Location {
+                    uri: uri.clone(),
+                    range: Range::new(range.start, range.start),
+                },
+            );
+        }
+
+        return Some(locations);
+    }
+
+    let symbol = backend.documents().get_and_then(uri, |doc| {
+        let offset = doc.offset_of(params.text_document_position.position);
+        locate_symbol(&doc.org, offset)
+    })?;
 
     let mut locations = vec![];
 
@@ -158,6 +197,22 @@ fn locate_symbol(org: &Org, offset: u32) -> Option<Symbol> {
     }
 }
 
+/// Returns the headline if offset is inside its title
+fn locate_headline(org: &Org, offset: u32) -> Option<Headline> {
+    let token = match org.document().syntax().token_at_offset(offset.into()) {
+        TokenAtOffset::None => return None,
+        TokenAtOffset::Single(t) => t,
+        TokenAtOffset::Between(t, _) => t,
+    };
+
+    token
+        .parent_ancestors()
+        .take_while(|n| n.kind() != SyntaxKind::HEADLINE)
+        .find(|n| n.kind() == SyntaxKind::HEADLINE_TITLE)?
+        .parent()
+        .and_then(Headline::cast)
+}
+
 #[test]
 fn test() {
     let org = "* TODO [#A] hello :abc: :edf:";
@@ -183,3 +238,19 @@ fn test() {
         assert!(matches!(symbol, Symbol::Tag(t) if t.text() == "edf"));
     }
 }
+
+#[test]
+fn test_locate_headline() {
+    let org = Org::parse("* TODO hello [[#a][a]]\n** world");
+
+    assert!(locate_headline(&org, 2).is_none());
+    assert_eq!(
+        locate_headline(&org, 9).unwrap().title_raw(),
+        "hello [[#a][a]]"
+    );
+    assert_eq!(
+        locate_headline(&org, 15).unwrap().title_raw(),
+        "hello [[#a][a]]"
+    );
+    assert_eq!(locate_headline(&org, 27).unwrap().title_raw(), "world");
+}
@@ -1,19 +1,34 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
 
 use lsp_types::Url;
 use orgize::rowan::TextRange;
 
 use crate::backend::{Backend, Documents};
+use crate::utils::language::{ExecuteOutput, Executor};
 
 #[derive(Default)]
 pub struct TestBackend {
     documents: Documents,
+    /// stdout returned by `execute`, in order
+    outputs: Mutex<VecDeque<String>>,
+    /// contents passed to `execute`, in order
+    executed: Mutex<Vec<String>>,
 }
 
 impl TestBackend {
     pub fn get(&self, url: &Url) -> String {
         self.documents.get_map(url, |d| d.org.to_org()).unwrap()
     }
+
+    /// Queues stdout of the next `execute`
+    pub fn push_output(&self, output: &str) {
+        self.outputs.lock().unwrap().push_back(output.to_string());
+    }
+
+    pub fn executed(&self) -> Vec<String> {
+        self.executed.lock().unwrap().clone()
+    }
 }
 
 impl Backend for TestBackend {
@@ -72,4 +87,18 @@ impl Backend for TestBackend {
         self.documents.insert(url.clone(), content);
         Ok(())
     }
+
+    async fn execute(&self, _: &Executor, content: &str) -> anyhow::Result<ExecuteOutput> {
+        self.executed.lock().unwrap().push(content.to_string());
+
+        let Some(stdout) = self.outputs.lock().unwrap().pop_front() else {
+            anyhow::bail!("no output queued");
+        };
+
+        Ok(ExecuteOutput {
+            stdout,
+            status: Some(0),
+            ..Default::default()
+        })
+    }
 }
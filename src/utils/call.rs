@@ -0,0 +1,334 @@
+use orgize::{
+    ast::SourceBlock,
+    rowan::{ast::AstNode, TextRange, TextSize},
+    SyntaxKind, SyntaxNode,
+};
+
+use crate::utils::src_block::block_name;
+
+/// `name[inside header](arguments)[end header]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub name: String,
+    pub inside: String,
+    pub arguments: String,
+    pub end: String,
+}
+
+/// `src_lang[parameters]{body}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineSrc {
+    pub language: String,
+    pub parameters: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvocationKind {
+    /// `#+CALL: name(arguments)`
+    CallLine(Call),
+    /// `call_name(arguments)`
+    InlineCall(Call),
+    InlineSrc(InlineSrc),
+}
+
+/// An evaluation that isn't a source block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    /// range of the call line excluding trailing newline, or of the inline object
+    pub range: TextRange,
+    pub kind: InvocationKind,
+}
+
+impl Invocation {
+    pub fn is_inline(&self) -> bool {
+        !matches!(self.kind, InvocationKind::CallLine(_))
+    }
+}
+
+/// Collects `#+CALL:` lines, inline calls and inline source blocks in document order
+pub fn collect_invocations(document: &SyntaxNode) -> Vec<Invocation> {
+    let text = document.to_string();
+    let base = document.text_range().start();
+
+    let mut invocations = vec![];
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if let Some(call) = parse_call_line(trimmed) {
+            let range = TextRange::at(
+                base + TextSize::from((start + indent) as u32),
+                TextSize::of(trimmed.trim_end()),
+            );
+
+            if is_element(document, range.start(), false) {
+                invocations.push(Invocation {
+                    range,
+                    kind: InvocationKind::CallLine(call),
+                });
+            }
+
+            continue;
+        }
+
+        let mut i = 0;
+        while i < line.len() {
+            let rest = &line[i..];
+
+            let boundary = !matches!(
+                line[..i].chars().next_back(),
+                Some(c) if c.is_alphanumeric() || c == '_'
+            );
+
+            let parsed = if !boundary {
+                None
+            } else if rest.starts_with("call_") {
+                parse_inline_call(rest).map(|(call, len)| (InvocationKind::InlineCall(call), len))
+            } else if rest.starts_with("src_") {
+                parse_inline_src(rest).map(|(src, len)| (InvocationKind::InlineSrc(src), len))
+            } else {
+                None
+            };
+
+            match parsed {
+                Some((kind, len)) => {
+                    let range = TextRange::at(
+                        base + TextSize::from((start + i) as u32),
+                        TextSize::from(len as u32),
+                    );
+
+                    if is_element(document, range.start(), true) {
+                        invocations.push(Invocation { range, kind });
+                    }
+
+                    i += len;
+                }
+                None => i += rest.chars().next().map_or(1, char::len_utf8),
+            }
+        }
+    }
+
+    invocations
+}
+
+/// Returns false if the offset is inside of a block, e.g. source code
+fn is_element(document: &SyntaxNode, offset: TextSize, inline: bool) -> bool {
+    let Some(token) = document.token_at_offset(offset).right_biased() else {
+        return false;
+    };
+
+    let mut ancestors = token.parent_ancestors();
+
+    if inline {
+        ancestors
+            .take_while(|n| n.kind() != SyntaxKind::BLOCK_CONTENT)
+            .any(|n| n.kind() == SyntaxKind::PARAGRAPH)
+    } else {
+        ancestors.all(|n| n.kind() != SyntaxKind::BLOCK_CONTENT)
+    }
+}
+
+/// Parses `#+CALL: name[inside](arguments) end`
+pub fn parse_call_line(line: &str) -> Option<Call> {
+    let line = line.trim();
+    let (key, value) = line.strip_prefix("#+")?.split_once(':')?;
+
+    if !key.eq_ignore_ascii_case("call") {
+        return None;
+    }
+
+    let value = value.trim();
+    let (mut call, len) = parse_call(value)?;
+    call.end = value[len..].trim().to_string();
+    Some(call)
+}
+
+/// Parses `call_name[inside](arguments)[end]`, returns the call and its length
+pub fn parse_inline_call(input: &str) -> Option<(Call, usize)> {
+    let rest = input.strip_prefix("call_")?;
+    let (mut call, mut len) = parse_call(rest)?;
+
+    if let Some((end, end_len)) = bracketed(&rest[len..], '[', ']') {
+        call.end = end.trim().to_string();
+        len += end_len;
+    }
+
+    Some((call, len + "call_".len()))
+}
+
+/// Parses `src_lang[parameters]{body}`, returns the source and its length
+pub fn parse_inline_src(input: &str) -> Option<(InlineSrc, usize)> {
+    let rest = input.strip_prefix("src_")?;
+
+    let language_len = rest
+        .find(|c: char| c == '[' || c == '{' || c.is_whitespace())
+        .filter(|&i| i > 0)?;
+    let mut len = language_len;
+
+    let mut parameters = String::new();
+    if let Some((inner, inner_len)) = bracketed(&rest[len..], '[', ']') {
+        parameters = inner.trim().to_string();
+        len += inner_len;
+    }
+
+    let (body, body_len) = bracketed(&rest[len..], '{', '}')?;
+    len += body_len;
+
+    Some((
+        InlineSrc {
+            language: rest[..language_len].to_string(),
+            parameters,
+            body: body.to_string(),
+        },
+        len + "src_".len(),
+    ))
+}
+
+/// Parses `name[inside](arguments)`, returns the call and its length
+fn parse_call(input: &str) -> Option<(Call, usize)> {
+    let name_len = input
+        .find(|c: char| c == '[' || c == '(' || c.is_whitespace())
+        .filter(|&i| i > 0)?;
+    let mut len = name_len;
+
+    let mut inside = String::new();
+    if let Some((inner, inner_len)) = bracketed(&input[len..], '[', ']') {
+        inside = inner.trim().to_string();
+        len += inner_len;
+    }
+
+    let (arguments, arguments_len) = bracketed(&input[len..], '(', ')')?;
+    len += arguments_len;
+
+    Some((
+        Call {
+            name: input[..name_len].to_string(),
+            inside,
+            arguments: arguments.trim().to_string(),
+            end: String::new(),
+        },
+        len,
+    ))
+}
+
+/// Parses balanced `open ... close` at the start of input, ignoring quoted text
+fn bracketed(input: &str, open: char, close: char) -> Option<(&str, usize)> {
+    let rest = input.strip_prefix(open)?;
+    let (mut depth, mut in_quote) = (0, false);
+
+    for (i, c) in rest.char_indices() {
+        match c {
+            '"' => in_quote = !in_quote,
+            c if c == open && !in_quote => depth += 1,
+            c if c == close && !in_quote => {
+                if depth == 0 {
+                    return Some((&rest[..i], i + open.len_utf8() + close.len_utf8()));
+                }
+                depth -= 1;
+            }
+            '\n' => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Finds the source block with `#+NAME: name` in the document containing `node`
+pub fn find_named_block(node: &SyntaxNode, name: &str) -> Option<SourceBlock> {
+    node.ancestors()
+        .last()?
+        .descendants()
+        .filter_map(SourceBlock::cast)
+        .find(|block| block_name(block.syntax()).as_deref() == Some(name))
+}
+
+/// Range of `{{{results(...)}}}` following an inline evaluation, including
+/// the leading space
+pub fn inline_results_range(text: &str, end: TextSize) -> Option<TextRange> {
+    let rest = text.get(usize::from(end)..)?;
+    let spaces = rest.len() - rest.trim_start_matches(' ').len();
+    let rest = &rest[spaces..];
+
+    if !rest.starts_with("{{{results(") {
+        return None;
+    }
+
+    let close = rest.find(")}}}")?;
+
+    if rest[..close].contains('\n') {
+        return None;
+    }
+
+    Some(TextRange::at(
+        end,
+        TextSize::from((spaces + close + ")}}}".len()) as u32),
+    ))
+}
+
+#[test]
+fn test() {
+    assert_eq!(
+        parse_call_line(r#"#+CALL: square[:results raw](x=2, y="a (b)") :results output"#),
+        Some(Call {
+            name: "square".into(),
+            inside: ":results raw".into(),
+            arguments: r#"x=2, y="a (b)""#.into(),
+            end: ":results output".into(),
+        })
+    );
+    assert_eq!(parse_call_line("#+CALL: square"), None);
+
+    assert_eq!(
+        parse_inline_call("call_double(n=4)[:results raw] rest"),
+        Some((
+            Call {
+                name: "double".into(),
+                inside: "".into(),
+                arguments: "n=4".into(),
+                end: ":results raw".into(),
+            },
+            "call_double(n=4)[:results raw]".len()
+        ))
+    );
+
+    assert_eq!(
+        parse_inline_src("src_python[:results raw]{print({1: 2})} text"),
+        Some((
+            InlineSrc {
+                language: "python".into(),
+                parameters: ":results raw".into(),
+                body: "print({1: 2})".into(),
+            },
+            "src_python[:results raw]{print({1: 2})}".len()
+        ))
+    );
+
+    let text = "a src_sh{echo 1} {{{results(=1=)}}} b";
+    assert_eq!(
+        inline_results_range(text, 16.into()),
+        Some(TextRange::new(16.into(), 35.into()))
+    );
+    assert_eq!(inline_results_range(text, 0.into()), None);
+
+    let org = orgize::Org::parse(
+        r#"#+CALL: a()
+
+text call_b() and src_js{1}, not xsrc_js{1}
+
+#+begin_src sh
+echo src_js{1}
+#+end_src
+"#,
+    );
+    let invocations = collect_invocations(org.document().syntax());
+    assert_eq!(invocations.len(), 3);
+    assert_eq!(invocations[0].range, TextRange::new(0.into(), 11.into()));
+    assert!(matches!(invocations[2].kind, InvocationKind::InlineSrc(_)));
+}
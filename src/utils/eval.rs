@@ -0,0 +1,132 @@
+use lsp_types::{MessageType, Url};
+
+use crate::backend::Backend;
+
+/// Value of `:eval` header argument
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvalPolicy {
+    Yes,
+    /// `never` or `no`
+    Never,
+    /// ask before evaluating
+    Query,
+    /// `no-export`, `never-export` or `query-export`, only restricts exporting
+    Export,
+}
+
+impl EvalPolicy {
+    pub fn parse(value: &str) -> EvalPolicy {
+        match value.trim() {
+            "never" | "no" => EvalPolicy::Never,
+            "query" => EvalPolicy::Query,
+            "no-export" | "never-export" | "query-export" => EvalPolicy::Export,
+            _ => EvalPolicy::Yes,
+        }
+    }
+}
+
+/// Directories whose files are allowed to execute source blocks
+#[derive(Default, Debug)]
+pub struct Trust {
+    /// trust every file, e.g. `orgwise execute-src-block --yes`
+    pub all: bool,
+    /// absolute paths, `~/` is expanded and relative paths are resolved
+    /// against home directory
+    pub directories: Vec<String>,
+}
+
+pub fn is_trusted<B: Backend>(backend: &B, url: &Url) -> bool {
+    let (all, directories) = backend
+        .documents()
+        .with_trust(|trust| (trust.all, trust.directories.clone()));
+
+    let home_dir = backend.home_dir();
+
+    all || directories.iter().any(|dir| {
+        let dir = if dir.ends_with('/') {
+            dir.clone()
+        } else {
+            format!("{dir}/")
+        };
+
+        let dir = match &home_dir {
+            Some(home_dir) => backend.resolve_in(&dir, home_dir),
+            // never resolve against the file being checked, otherwise
+            // `./` would trust every file
+            None if dir.starts_with('/') => backend.resolve_in(&dir, url),
+            None => return false,
+        };
+
+        dir.is_ok_and(|dir| url.as_str().starts_with(dir.as_str()))
+    })
+}
+
+/// Asks user for confirmation, returns true if accepted
+pub async fn confirm<B: Backend>(backend: &B, message: String) -> bool {
+    let action = backend
+        .show_message_request(
+            MessageType::WARNING,
+            message,
+            vec!["Execute".into(), "Cancel".into()],
+        )
+        .await;
+
+    matches!(action, Ok(Some(action)) if action == "Execute")
+}
+
+/// Fails unless the file is trusted or user confirms executing it
+pub async fn ensure_trusted<B: Backend>(backend: &B, url: &Url) -> anyhow::Result<()> {
+    if is_trusted(backend, url)
+        || confirm(
+            backend,
+            format!("{url} is not in a trusted directory. Execute its source blocks?"),
+        )
+        .await
+    {
+        return Ok(());
+    }
+
+    anyhow::bail!(
+        "refused to execute source blocks of untrusted file {url}, \
+         add its directory to trusted directories or confirm executing"
+    )
+}
+
+#[test]
+fn test() {
+    use crate::test::TestBackend;
+
+    let backend = TestBackend::default();
+    backend.documents().with_trust(|trust| {
+        trust.directories = vec!["/home/user/notes".into()];
+    });
+
+    assert!(is_trusted(
+        &backend,
+        &Url::parse("file:///home/user/notes/a.org").unwrap()
+    ));
+    assert!(!is_trusted(
+        &backend,
+        &Url::parse("file:///home/user/notes-b/a.org").unwrap()
+    ));
+    assert!(!is_trusted(
+        &backend,
+        &Url::parse("file:///tmp/a.org").unwrap()
+    ));
+
+    backend.documents().with_trust(|trust| {
+        trust.directories = vec!["./".into(), "notes/".into()];
+    });
+
+    assert!(!is_trusted(
+        &backend,
+        &Url::parse("file:///tmp/a.org").unwrap()
+    ));
+    assert!(!is_trusted(
+        &backend,
+        &Url::parse("file:///tmp/notes/a.org").unwrap()
+    ));
+
+    assert_eq!(EvalPolicy::parse("never"), EvalPolicy::Never);
+    assert_eq!(EvalPolicy::parse("no-export"), EvalPolicy::Export);
+}
@@ -1,9 +1,52 @@
 use lsp_types::Position;
 use orgize::ast::Headline;
-use orgize::rowan::ast::AstNode;
+use orgize::export::{from_fn_with_ctx, Container, Event};
+use orgize::rowan::{ast::AstNode, TextRange, TextSize};
+use orgize::{SyntaxKind, SyntaxNode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
 use crate::backend::OrgDocument;
 
+/// Describes how a command should locate its target headline
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum HeadlineLocator {
+    /// value of the `ID` or `CUSTOM_ID` property
+    Id { id: String },
+    /// titles from the top-level headline down to the target,
+    /// e.g. `["Projects", "Website", "Deploy"]`
+    Outline { outline: Vec<String> },
+    /// one-based line number
+    Line { line: u32 },
+}
+
+impl HeadlineLocator {
+    pub fn find(&self, doc: &OrgDocument) -> Option<Headline> {
+        match self {
+            HeadlineLocator::Id { id } => find_headline_by_id(doc, id),
+            HeadlineLocator::Outline { outline } => find_headline_by_outline(doc, outline),
+            HeadlineLocator::Line { line } => {
+                if *line == 0 || *line as usize > doc.line_numbers() {
+                    None
+                } else {
+                    find_headline(doc, *line)
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for HeadlineLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeadlineLocator::Id { id } => write!(f, "with id {id:?}"),
+            HeadlineLocator::Outline { outline } => write!(f, "at {:?}", outline.join("/")),
+            HeadlineLocator::Line { line } => write!(f, "at line {line}"),
+        }
+    }
+}
+
 pub fn find_headline(doc: &OrgDocument, line: u32) -> Option<Headline> {
     let offset = doc.offset_of(Position {
         line: line - 1,
@@ -25,6 +68,95 @@ pub fn find_headline(doc: &OrgDocument, line: u32) -> Option<Headline> {
     }
 }
 
+pub fn find_headline_by_id(doc: &OrgDocument, id: &str) -> Option<Headline> {
+    let mut found = None;
+
+    doc.traverse(&mut from_fn_with_ctx(|event, ctx| match event {
+        Event::Enter(Container::Headline(headline)) => {
+            if headline_id(&headline).as_deref() == Some(id)
+                || headline_property(&headline, "CUSTOM_ID").as_deref() == Some(id)
+            {
+                found = Some(headline);
+                ctx.stop();
+            }
+        }
+        Event::Enter(Container::Section(_)) => ctx.skip(),
+        _ => {}
+    }));
+
+    found
+}
+
+pub fn find_headline_by_outline(doc: &OrgDocument, outline: &[String]) -> Option<Headline> {
+    let mut node = doc.org.document().syntax().clone();
+    let mut found = None;
+
+    for title in outline {
+        let headline = node
+            .children()
+            .filter_map(Headline::cast)
+            .find(|hdl| hdl.title_raw().trim() == title.trim())?;
+
+        node = headline.syntax().clone();
+        found = Some(headline);
+    }
+
+    found
+}
+
+/// Returns the value of given property, key is case-insensitive
+pub fn headline_property(headline: &Headline, key: &str) -> Option<String> {
+    headline
+        .properties()?
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+pub fn headline_id(headline: &Headline) -> Option<String> {
+    headline_property(headline, "ID")
+}
+
+/// Returns the text and the position for inserting a new property into headline,
+/// property drawer will be created if not existed
+pub fn insert_property(headline: &Headline, key: &str, value: &str) -> (String, TextRange) {
+    let property = format!("{:<10} {value}\n", format!(":{key}:"));
+
+    if let Some(drawer) = headline.properties() {
+        let drawer = drawer.syntax();
+        let text = drawer.to_string();
+        let offset = text.find('\n').map(|i| i + 1).unwrap_or(text.len());
+        let position = drawer.text_range().start() + TextSize::new(offset as u32);
+
+        return (property, TextRange::empty(position));
+    }
+
+    let position = headline
+        .syntax()
+        .children()
+        .find(|n| n.kind() == SyntaxKind::PLANNING)
+        .map(|n| n.text_range().end())
+        .or_else(|| {
+            headline
+                .syntax()
+                .children_with_tokens()
+                .find(|t| t.kind() == SyntaxKind::NEW_LINE)
+                .map(|t| t.text_range().end())
+        });
+
+    match position {
+        Some(position) => (
+            format!(":PROPERTIES:\n{property}:END:\n"),
+            TextRange::empty(position),
+        ),
+        None => (
+            format!("\n:PROPERTIES:\n{property}:END:\n"),
+            TextRange::empty(headline.end()),
+        ),
+    }
+}
+
 pub fn headline_slug(headline: &Headline) -> String {
     headline.title().fold(String::new(), |mut acc, elem| {
         for ch in elem.to_string().chars().filter(|c| c.is_ascii_graphic()) {
@@ -33,3 +165,70 @@ pub fn headline_slug(headline: &Headline) -> String {
         acc
     })
 }
+
+/// Returns one-based line numbers of the innermost headlines touched by given ranges
+pub fn headline_lines_in_ranges(doc: &OrgDocument, ranges: &[TextRange]) -> Vec<u32> {
+    let mut lines = vec![];
+
+    let touches = |range: TextRange, r: &TextRange| {
+        if r.is_empty() {
+            range.contains(r.start())
+        } else {
+            range.intersect(*r).map_or(false, |i| !i.is_empty())
+        }
+    };
+
+    let mut stack: Vec<SyntaxNode> = vec![doc.org.document().syntax().clone()];
+
+    while let Some(node) = stack.pop() {
+        for hdl in node.children().filter_map(Headline::cast) {
+            let range = hdl.text_range();
+
+            let touched = ranges.iter().filter(|&r| touches(range, r));
+
+            let mut touched_self = false;
+            for r in touched {
+                let covered_by_child = hdl
+                    .syntax()
+                    .children()
+                    .filter_map(Headline::cast)
+                    .any(|child| child.text_range().contains_range(*r));
+
+                if !covered_by_child {
+                    touched_self = true;
+                }
+            }
+
+            if touched_self {
+                lines.push(doc.line_of(hdl.start().into()) + 1);
+            }
+
+            if ranges.iter().any(|r| touches(range, r)) {
+                stack.push(hdl.syntax().clone());
+            }
+        }
+    }
+
+    lines.sort_unstable();
+    lines.dedup();
+    lines
+}
+
+#[test]
+fn test_headline_lines_in_ranges() {
+    use orgize::ParseConfig;
+
+    let doc = OrgDocument::new("* a\n** b\ntext\n* c\n", ParseConfig::default());
+
+    // inside the section of `b`
+    assert_eq!(
+        headline_lines_in_ranges(&doc, &[TextRange::new(10.into(), 12.into())]),
+        vec![2]
+    );
+
+    // spanning both `b` and `c`
+    assert_eq!(
+        headline_lines_in_ranges(&doc, &[TextRange::new(10.into(), 16.into())]),
+        vec![1, 2, 4]
+    );
+}
@@ -0,0 +1,150 @@
+use lsp_types::{Range, Url};
+use orgize::export::{from_fn_with_ctx, Container, Event};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::backend::OrgDocument;
+use crate::utils::headline::headline_property;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IdLocation {
+    pub url: Url,
+    /// range of the whole headline
+    pub range: Range,
+}
+
+/// Maps `ID` and `CUSTOM_ID` properties to their headlines
+#[derive(Default)]
+pub struct IdIndex {
+    /// ids found in loaded documents
+    loaded: HashMap<String, IdLocation>,
+    /// ids read from the locations file, might be outdated
+    cached: HashMap<String, Url>,
+    /// documents found in `loaded`, their cached ids are ignored
+    indexed: HashSet<Url>,
+    /// where to persist id locations, similar to `org-id-locations-file`
+    pub locations_file: Option<Url>,
+    pub locations_file_loaded: bool,
+}
+
+impl IdIndex {
+    /// Replaces all ids previously found in given document
+    pub fn reindex(&mut self, url: &Url, doc: &OrgDocument) {
+        self.loaded.retain(|_, location| &location.url != url);
+        self.indexed.insert(url.clone());
+
+        for (id, range) in collect_ids(doc) {
+            self.loaded.insert(
+                id,
+                IdLocation {
+                    url: url.clone(),
+                    range,
+                },
+            );
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&IdLocation> {
+        self.loaded.get(id)
+    }
+
+    /// Returns the url of document containing given id, either loaded or not
+    pub fn get_url(&self, id: &str) -> Option<&Url> {
+        self.loaded
+            .get(id)
+            .map(|location| &location.url)
+            .or_else(|| {
+                self.cached
+                    .get(id)
+                    .filter(|url| !self.indexed.contains(*url))
+            })
+    }
+
+    pub fn extend_cached(&mut self, cached: HashMap<String, Url>) {
+        self.cached.extend(cached);
+    }
+
+    /// Merges loaded ids into cached ones, ids removed from loaded documents are dropped
+    pub fn to_cached(&self) -> HashMap<String, Url> {
+        self.cached
+            .iter()
+            .filter(|(_, url)| !self.indexed.contains(*url))
+            .map(|(id, url)| (id.clone(), url.clone()))
+            .chain(
+                self.loaded
+                    .iter()
+                    .map(|(id, location)| (id.clone(), location.url.clone())),
+            )
+            .collect()
+    }
+}
+
+pub fn collect_ids(doc: &OrgDocument) -> Vec<(String, Range)> {
+    let mut ids = vec![];
+
+    doc.traverse(&mut from_fn_with_ctx(|event, ctx| match event {
+        Event::Enter(Container::Headline(headline)) => {
+            let range = doc.range_of(headline.text_range());
+
+            for key in ["ID", "CUSTOM_ID"] {
+                if let Some(id) = headline_property(&headline, key) {
+                    ids.push((id, range));
+                }
+            }
+        }
+        Event::Enter(Container::Section(_)) => ctx.skip(),
+        _ => {}
+    }));
+
+    ids
+}
+
+#[test]
+fn test() {
+    use orgize::ParseConfig;
+
+    let url = Url::parse("test://test.org").unwrap();
+    let doc = OrgDocument::new(
+        "* a\n:PROPERTIES:\n:ID: a\n:END:\n** b\n:PROPERTIES:\n:CUSTOM_ID: b\n:END:\n",
+        ParseConfig::default(),
+    );
+
+    let mut index = IdIndex::default();
+    index.extend_cached(HashMap::from([
+        ("c".to_string(), url.clone()),
+        ("d".to_string(), Url::parse("test://other.org").unwrap()),
+    ]));
+    index.reindex(&url, &doc);
+
+    assert_eq!(index.get("a").unwrap().range.start.line, 0);
+    assert_eq!(index.get("b").unwrap().range.start.line, 4);
+    assert!(index.get("c").is_none());
+
+    let cached = index.to_cached();
+    assert_eq!(cached.len(), 3);
+    assert!(!cached.contains_key("c"));
+    assert!(cached.contains_key("d"));
+
+    // document no longer containing any id
+    index.reindex(&url, &OrgDocument::new("* a\n", ParseConfig::default()));
+    assert!(index.get_url("a").is_none());
+    assert_eq!(index.to_cached().into_keys().collect::<Vec<_>>(), vec!["d"]);
+}
+
+#[test]
+fn lazy_reindex() {
+    use crate::backend::Documents;
+    use lsp_types::Position;
+
+    let url = Url::parse("test://test.org").unwrap();
+    let documents = Documents::default();
+    documents.insert(url.clone(), "* a\n:PROPERTIES:\n:ID: a\n:END:\n");
+    assert!(documents.with_ids(|ids| ids.get("a").is_some()));
+
+    documents.update(
+        url.clone(),
+        Some(Range::new(Position::new(2, 5), Position::new(2, 6))),
+        "b",
+    );
+    assert!(documents.with_ids(|ids| ids.get("a").is_none() && ids.get("b").is_some()));
+}
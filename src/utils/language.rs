@@ -0,0 +1,309 @@
+use lsp_types::Url;
+use orgize::SyntaxNode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::backend::Documents;
+use crate::utils::src_block::{find_header_argument, property_keyword_language};
+
+/// Settings of a source block language
+///
+/// ```toml
+/// [languages.rust]
+/// build = "rustc {file} -o {output}"
+/// command = "{output}"
+/// extension = "rs"
+/// comments = ["//", ""]
+/// timeout = 30
+/// ```
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct LanguageConfig {
+    /// command for running the source file, `{file}` is replaced with its path
+    /// and `{output}` with the path of build output
+    pub command: Option<String>,
+    /// command for compiling the source file before running
+    pub build: Option<String>,
+    /// extension of the source file
+    pub extension: Option<String>,
+    /// pass block content through stdin instead of file path
+    pub stdin: Option<bool>,
+    /// line comment syntax, e.g. `["<!--", "-->"]`
+    pub comments: Option<(String, String)>,
+    /// seconds before execution is killed
+    pub timeout: Option<u64>,
+}
+
+/// Content of `orgwise.toml`
+#[derive(Deserialize, Debug, Default)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub languages: HashMap<String, LanguageConfig>,
+    /// directories allowed to execute source blocks
+    #[serde(default)]
+    pub trusted_directories: Vec<String>,
+    /// org files whose named source blocks are callable from any document
+    #[serde(default)]
+    pub library_files: Vec<String>,
+    /// seconds before execution is killed, unless set for the language
+    pub timeout: Option<u64>,
+}
+
+/// A resolved command for executing source blocks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Executor {
+    pub command: Vec<String>,
+    pub build: Option<Vec<String>>,
+    pub extension: String,
+    pub stdin: bool,
+    /// seconds before execution is killed
+    pub timeout: Option<u64>,
+    /// working directory, defaults to a temporary directory
+    pub dir: Option<Url>,
+    /// extra arguments passed to command
+    pub cmdline: Vec<String>,
+    /// environment variables
+    pub env: Vec<(String, String)>,
+}
+
+/// Outcome of executing a source block
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExecuteOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// exit code, `None` if terminated by signal
+    pub status: Option<i32>,
+    pub duration: Duration,
+}
+
+impl ExecuteOutput {
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+}
+
+impl LanguageConfig {
+    /// Fields of `other` take precedence
+    pub fn merge(self, other: LanguageConfig) -> LanguageConfig {
+        LanguageConfig {
+            command: other.command.or(self.command),
+            build: other.build.or(self.build),
+            extension: other.extension.or(self.extension),
+            stdin: other.stdin.or(self.stdin),
+            comments: other.comments.or(self.comments),
+            timeout: other.timeout.or(self.timeout),
+        }
+    }
+
+    pub fn comments(&self) -> Option<(&str, &str)> {
+        self.comments
+            .as_ref()
+            .map(|(begin, end)| (begin.as_str(), end.as_str()))
+    }
+
+    pub fn executor(&self) -> Option<Executor> {
+        let command = split_args(self.command.as_deref()?);
+
+        if command.is_empty() {
+            return None;
+        }
+
+        Some(Executor {
+            command,
+            build: self.build.as_deref().map(split_args),
+            extension: self.extension.clone().unwrap_or_default(),
+            stdin: self.stdin.unwrap_or_default(),
+            timeout: self.timeout,
+            dir: None,
+            cmdline: vec![],
+            env: vec![],
+        })
+    }
+}
+
+impl Executor {
+    /// Substitutes `{file}` and `{output}` in given template
+    ///
+    /// File path is appended if the command neither uses `{file}` nor reads stdin.
+    pub fn args(&self, template: &[String], file: &str, output: &str) -> Vec<String> {
+        let mut args: Vec<_> = template
+            .iter()
+            .map(|arg| arg.replace("{file}", file).replace("{output}", output))
+            .collect();
+
+        let uses_file = self.stdin
+            || self.build.is_some()
+            || template
+                .iter()
+                .any(|arg| arg.contains("{file}") || arg.contains("{output}"));
+
+        if !uses_file {
+            args.push(file.to_string());
+        }
+
+        args
+    }
+}
+
+/// Resolves language settings from built-in defaults, configuration and
+/// `#+PROPERTY: header-args:lang` of the document containing `node`
+pub fn language_config(documents: &Documents, language: &str, node: &SyntaxNode) -> LanguageConfig {
+    let configured = documents.with_languages(|languages| languages.get(language).cloned());
+
+    let mut config = builtin(language).merge(configured.unwrap_or_default());
+    config.timeout = config.timeout.or(documents.default_timeout());
+
+    if let Some(property) = property_keyword_language(node, language) {
+        config = config.merge(from_header_args(&property));
+    }
+
+    config
+}
+
+fn from_header_args(args: &str) -> LanguageConfig {
+    let get = |key: &str| {
+        find_header_argument(args, key).map(|value| value.trim_matches('"').to_string())
+    };
+
+    LanguageConfig {
+        command: get(":command"),
+        build: get(":build"),
+        extension: get(":extension"),
+        stdin: get(":stdin").map(|value| value == "yes"),
+        comments: get(":comment-start")
+            .map(|begin| (begin, get(":comment-end").unwrap_or_default())),
+        timeout: get(":timeout").and_then(|value| value.parse().ok()),
+    }
+}
+
+/// Splits by whitespace, keeping quoted text together
+pub fn split_args(input: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+
+    for c in input.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => arg.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(arg.take()),
+            (None, c) => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    args.extend(arg);
+    args
+}
+
+fn builtin(language: &str) -> LanguageConfig {
+    let comments = match language {
+        "c" | "cpp" | "c++" | "go" | "js" | "javascript" | "ts" | "typescript" | "rust"
+        | "vera" | "jsonc" => Some(("//", "")),
+        "toml" | "tml" | "yaml" | "yml" | "conf" | "gitconfig" | "conf-toml" | "sh" | "shell"
+        | "bash" | "zsh" | "fish" | "py" | "python" => Some(("#", "")),
+        "lua" | "sql" => Some(("--", "")),
+        "lisp" | "emacs-lisp" | "elisp" => Some((";;", "")),
+        "xml" | "html" | "svg" => Some(("<!--", "-->")),
+        _ => None,
+    };
+
+    let (command, build, extension) = match language {
+        "js" | "javascript" => (Some("node"), None, "js"),
+        "sh" | "bash" => (Some("bash"), None, "sh"),
+        "py" | "python" => (Some("python"), None, "py"),
+        "fish" => (Some("fish"), None, "fish"),
+        "rust" => (Some("{output}"), Some("rustc {file} -o {output}"), "rs"),
+        "c" => (Some("{output}"), Some("cc {file} -o {output}"), "c"),
+        "cpp" | "c++" => (Some("{output}"), Some("c++ {file} -o {output}"), "cpp"),
+        "go" => (Some("go run {file}"), None, "go"),
+        _ => (None, None, ""),
+    };
+
+    LanguageConfig {
+        command: command.map(String::from),
+        build: build.map(String::from),
+        extension: (!extension.is_empty()).then(|| extension.to_string()),
+        stdin: None,
+        comments: comments.map(|(begin, end)| (begin.to_string(), end.to_string())),
+        timeout: None,
+    }
+}
+
+#[test]
+fn test() {
+    use orgize::{ast::SourceBlock, rowan::ast::AstNode, Org};
+
+    let documents = Documents::default();
+    documents.with_languages(|languages| {
+        languages.insert(
+            "python".into(),
+            LanguageConfig {
+                command: Some("python3".into()),
+                ..Default::default()
+            },
+        )
+    });
+
+    let org = Org::parse(
+        r#"#+PROPERTY: header-args:js :command "deno run {file}" :comment-start "/*" :comment-end "*/"
+
+#+begin_src js
+#+end_src
+"#,
+    );
+    let block = org.first_node::<SourceBlock>().unwrap();
+
+    let python = language_config(&documents, "python", block.syntax());
+    assert_eq!(
+        python.executor().unwrap(),
+        Executor {
+            command: vec!["python3".into()],
+            build: None,
+            extension: "py".into(),
+            stdin: false,
+            timeout: None,
+            dir: None,
+            cmdline: vec![],
+            env: vec![],
+        }
+    );
+
+    let js = language_config(&documents, "js", block.syntax());
+    let executor = js.executor().unwrap();
+    assert_eq!(
+        executor.args(&executor.command, "a.js", ""),
+        vec!["deno", "run", "a.js"]
+    );
+    assert_eq!(js.comments(), Some(("/*", "*/")));
+
+    let rust = language_config(&documents, "rust", block.syntax())
+        .executor()
+        .unwrap();
+    assert_eq!(
+        rust.args(rust.build.as_ref().unwrap(), "main.rs", "main"),
+        vec!["rustc", "main.rs", "-o", "main"]
+    );
+    assert_eq!(rust.args(&rust.command, "main.rs", "main"), vec!["main"]);
+
+    documents.set_default_timeout(Some(5));
+    documents.with_languages(|languages| {
+        languages.get_mut("python").unwrap().timeout = Some(10);
+    });
+    let timeout = |language| {
+        language_config(&documents, language, block.syntax())
+            .executor()
+            .unwrap()
+            .timeout
+    };
+    assert_eq!(timeout("rust"), Some(5));
+    assert_eq!(timeout("python"), Some(10));
+
+    assert_eq!(
+        split_args(r#"-v  --name "a b" X='c d' """#),
+        vec!["-v", "--name", "a b", "X=c d", ""]
+    );
+}
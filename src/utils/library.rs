@@ -0,0 +1,166 @@
+use lsp_types::{MessageType, Url};
+use orgize::{ast::SourceBlock, rowan::ast::AstNode, Org};
+
+use crate::backend::{Backend, Documents};
+use crate::utils::noweb::NowebBlock;
+use crate::utils::src_block::{block_name, collect_src_blocks};
+
+/// Named source blocks from library files, similar to `org-babel-library-of-babel`
+#[derive(Default)]
+pub struct Library {
+    /// configured library files, `~/` is expanded
+    pub files: Vec<String>,
+    pub loaded: bool,
+    /// resolved urls of library files, including ones failed to load
+    urls: Vec<Url>,
+    documents: Vec<(Url, Org)>,
+}
+
+impl Library {
+    /// Calls `f` with the first library block named `name`
+    pub fn find<T>(&self, name: &str, f: impl FnOnce(&Url, &SourceBlock) -> T) -> Option<T> {
+        self.documents.iter().find_map(|(url, org)| {
+            collect_src_blocks(org)
+                .into_iter()
+                .find(|block| block_name(block.syntax()).as_deref() == Some(name))
+                .map(|block| f(url, &block))
+        })
+    }
+
+    /// Makes library files reload on next use if `url` is one of them,
+    /// returns true if it is
+    pub fn invalidate(&mut self, url: &Url) -> bool {
+        let found = self.urls.contains(url);
+        if found {
+            self.loaded = false;
+        }
+        found
+    }
+
+    /// Calls `f` with every named library block, blocks shadowed by an
+    /// earlier one with the same name are skipped
+    pub fn for_each(&self, mut f: impl FnMut(&Url, &str, &SourceBlock)) {
+        let mut seen = vec![];
+
+        for (url, org) in &self.documents {
+            for block in collect_src_blocks(org) {
+                let Some(name) = block_name(block.syntax()) else {
+                    continue;
+                };
+
+                if !seen.contains(&name) {
+                    f(url, &name, &block);
+                    seen.push(name);
+                }
+            }
+        }
+    }
+}
+
+/// Reads and parses library files, only once
+pub async fn load_library<B: Backend>(backend: &B) {
+    let Some(files) = backend.documents().with_library(|library| {
+        if library.loaded {
+            None
+        } else {
+            library.loaded = true;
+            Some(library.files.clone())
+        }
+    }) else {
+        return;
+    };
+
+    let mut urls = Vec::with_capacity(files.len());
+    let mut documents = Vec::with_capacity(files.len());
+
+    for file in files {
+        let url = match backend.home_dir() {
+            Some(home_dir) => backend.resolve_in(&file, &home_dir),
+            None => Url::parse(&file).map_err(Into::into),
+        };
+
+        let result = match url {
+            Ok(url) => {
+                urls.push(url.clone());
+                backend
+                    .read_to_string(&url)
+                    .await
+                    .map(|content| (url, content))
+            }
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok((url, content)) => documents.push((url, Org::parse(content))),
+            Err(err) => {
+                backend
+                    .log_message(
+                        MessageType::WARNING,
+                        format!("Failed to load library file {file:?}: {err}"),
+                    )
+                    .await
+            }
+        }
+    }
+
+    backend.documents().with_library(|library| {
+        library.urls = urls;
+        library.documents = documents;
+    });
+}
+
+/// Library blocks that can be referenced by name in noweb references
+pub fn library_blocks(documents: &Documents) -> Vec<NowebBlock> {
+    documents.with_library(|library| {
+        let mut blocks = vec![];
+        library.for_each(|_, _, block| {
+            let mut block = NowebBlock::new(block, documents);
+            // library blocks are only referenced by name
+            block.noweb_ref = None;
+            blocks.push(block);
+        });
+        blocks
+    })
+}
+
+#[test]
+fn test() {
+    let mut library = Library::default();
+    library.documents.push((
+        Url::parse("file:///lib.org").unwrap(),
+        Org::parse(
+            r#"#+NAME: square
+#+begin_src python :var x=2 :results value
+return x * x
+#+end_src
+
+#+NAME: square
+#+begin_src js
+#+end_src
+
+#+begin_src sh
+#+end_src
+"#,
+        ),
+    ));
+
+    let mut names = vec![];
+    library.for_each(|_, name, _| names.push(name.to_string()));
+    assert_eq!(names, vec!["square"]);
+
+    assert_eq!(
+        library.find("square", |_, block| block
+            .language()
+            .unwrap_or_default()
+            .to_string()),
+        Some("python".into())
+    );
+    assert!(library.find("cube", |_, _| ()).is_none());
+
+    library.loaded = true;
+    library.urls.push(Url::parse("file:///lib.org").unwrap());
+    assert!(!library.invalidate(&Url::parse("file:///a.org").unwrap()));
+    assert!(library.loaded);
+    assert!(library.invalidate(&Url::parse("file:///lib.org").unwrap()));
+    assert!(!library.loaded);
+}
@@ -0,0 +1,246 @@
+use lsp_types::{Range, Url};
+use orgize::{
+    ast::Link,
+    export::{from_fn_with_ctx, Container, Event},
+    rowan::ast::{support, AstNode},
+    SyntaxKind,
+};
+use serde::Serialize;
+
+use crate::backend::{Backend, OrgDocument};
+use crate::utils::headline::{headline_property, headline_slug};
+
+/// Where a link points to
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinkTarget {
+    pub url: Url,
+    /// one-based line of target headline, `None` if it points to the whole file
+    pub line: Option<u32>,
+}
+
+/// Where a link is written
+#[derive(Serialize, Clone, Debug)]
+pub struct LinkSource {
+    pub url: Url,
+    pub range: Range,
+    /// one-based line of the headline containing this link
+    pub headline: Option<u32>,
+    /// the line containing this link
+    pub context: String,
+}
+
+enum RawTarget {
+    Id(String),
+    File { url: Url, search: Option<String> },
+}
+
+/// Collects all links between loaded documents, external links are ignored
+pub fn collect_links<B: Backend>(backend: &B) -> Vec<(LinkSource, LinkTarget)> {
+    let mut raw_links = vec![];
+
+    backend.documents().for_each(|url, doc| {
+        let mut headlines: Vec<u32> = vec![];
+
+        doc.traverse(&mut from_fn_with_ctx(|event, ctx| match event {
+            Event::Enter(Container::Headline(headline)) => {
+                headlines.push(doc.line_of(headline.start().into()) + 1);
+            }
+            Event::Leave(Container::Headline(_)) => {
+                headlines.pop();
+            }
+            Event::Enter(Container::Link(link)) => {
+                if let Some(link) = raw_link(backend, doc, url, &link, headlines.last().copied()) {
+                    raw_links.push(link);
+                }
+                ctx.skip();
+            }
+            _ => {}
+        }));
+    });
+
+    // resolve targets after iterating, since we might need to access other documents
+    raw_links
+        .into_iter()
+        .filter_map(|(source, target)| Some((source, resolve_target(backend, target)?)))
+        .collect()
+}
+
+/// Returns links pointing to given target
+pub fn collect_backlinks<B: Backend>(backend: &B, target: &LinkTarget) -> Vec<LinkSource> {
+    collect_links(backend)
+        .into_iter()
+        .filter(|(_, t)| t.url == target.url && (target.line.is_none() || t.line == target.line))
+        .map(|(source, _)| source)
+        .collect()
+}
+
+fn raw_link<B: Backend>(
+    backend: &B,
+    doc: &OrgDocument,
+    base: &Url,
+    link: &Link,
+    headline: Option<u32>,
+) -> Option<(LinkSource, RawTarget)> {
+    let path = support::token(link.syntax(), SyntaxKind::LINK_PATH)
+        .or_else(|| support::token(link.syntax(), SyntaxKind::TEXT))?;
+
+    let target = parse_link_path(backend, base, path.text())?;
+
+    let range = doc.range_of(link.syntax().text_range());
+
+    let line = range.start.line as usize;
+    let start = doc.line_starts[line] as usize;
+    let end = doc
+        .line_starts
+        .get(line + 1)
+        .map(|i| *i as usize)
+        .unwrap_or(doc.text.len());
+
+    Some((
+        LinkSource {
+            url: base.clone(),
+            range,
+            headline,
+            context: doc.text[start..end].trim().to_string(),
+        },
+        target,
+    ))
+}
+
+fn parse_link_path<B: Backend>(backend: &B, base: &Url, path: &str) -> Option<RawTarget> {
+    if let Some(id) = path.strip_prefix("id:") {
+        return Some(RawTarget::Id(id.trim().to_string()));
+    }
+
+    if path.starts_with('#') || path.starts_with('*') {
+        return Some(RawTarget::File {
+            url: base.clone(),
+            search: Some(path.to_string()),
+        });
+    }
+
+    let path = if let Some(file) = path.strip_prefix("file:") {
+        file
+    } else if path.starts_with('/')
+        || path.starts_with("./")
+        || path.starts_with("../")
+        || path.starts_with("~/")
+    {
+        path
+    } else {
+        return None;
+    };
+
+    let (path, search) = match path.split_once("::") {
+        Some((path, search)) => (path, Some(search.to_string())),
+        None => (path, None),
+    };
+
+    Some(RawTarget::File {
+        url: backend.resolve_in(path, base).ok()?,
+        search,
+    })
+}
+
+fn resolve_target<B: Backend>(backend: &B, target: RawTarget) -> Option<LinkTarget> {
+    match target {
+        RawTarget::Id(id) => backend.documents().with_ids(|ids| {
+            ids.get(&id).map(|location| LinkTarget {
+                url: location.url.clone(),
+                line: Some(location.range.start.line + 1),
+            })
+        }),
+        RawTarget::File { url, search } => {
+            let line = search.and_then(|search| {
+                backend
+                    .documents()
+                    .get_and_then(&url, |doc| search_headline(doc, &search))
+            });
+
+            Some(LinkTarget { url, line })
+        }
+    }
+}
+
+/// Finds headline by `*title` or `#custom-id` search option, returns its one-based line
+fn search_headline(doc: &OrgDocument, search: &str) -> Option<u32> {
+    let mut found = None;
+
+    doc.traverse(&mut from_fn_with_ctx(|event, ctx| match event {
+        Event::Enter(Container::Headline(headline)) => {
+            let is_match = if let Some(title) = search.strip_prefix('*') {
+                headline.title_raw().trim() == title.trim()
+            } else if let Some(id) = search.strip_prefix('#') {
+                headline_property(&headline, "CUSTOM_ID").as_deref() == Some(id)
+                    || headline_slug(&headline) == id
+            } else {
+                false
+            };
+
+            if is_match {
+                found = Some(doc.line_of(headline.start().into()) + 1);
+                ctx.stop();
+            }
+        }
+        Event::Enter(Container::Section(_)) => ctx.skip(),
+        _ => {}
+    }));
+
+    found
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test() {
+    use crate::test::TestBackend;
+
+    let backend = TestBackend::default();
+    let a = Url::parse("test://test.org/a.org").unwrap();
+    let b = Url::parse("test://test.org/b.org").unwrap();
+
+    backend.documents().insert(
+        a.clone(),
+        r#"* a
+see [[file:./b.org::*Deploy][deploy]] and [[#custom][custom]]
+** custom
+:PROPERTIES:
+:CUSTOM_ID: custom
+:END:
+[[id:b-id]] [[https://example.com]] [[./b.org]]
+"#,
+    );
+    backend.documents().insert(
+        b.clone(),
+        "* Website\n* Deploy\n:PROPERTIES:\n:ID: b-id\n:END:\n",
+    );
+
+    let links = collect_links(&backend);
+    assert_eq!(links.len(), 4);
+
+    let backlinks = collect_backlinks(
+        &backend,
+        &LinkTarget {
+            url: b.clone(),
+            line: Some(2),
+        },
+    );
+    assert_eq!(backlinks.len(), 2);
+    assert_eq!(backlinks[0].headline, Some(1));
+    assert_eq!(
+        backlinks[0].context,
+        "see [[file:./b.org::*Deploy][deploy]] and [[#custom][custom]]"
+    );
+    assert_eq!(backlinks[1].headline, Some(3));
+
+    let backlinks = collect_backlinks(&backend, &LinkTarget { url: b, line: None });
+    assert_eq!(backlinks.len(), 3);
+
+    let backlinks = collect_backlinks(
+        &backend,
+        &LinkTarget {
+            url: a,
+            line: Some(3),
+        },
+    );
+    assert_eq!(backlinks.len(), 1);
+}
@@ -1,5 +1,15 @@
+pub mod call;
 pub mod clocking;
+pub mod eval;
 pub mod headline;
+pub mod id;
+pub mod language;
+pub mod library;
+pub mod link;
+pub mod noweb;
 pub mod src_block;
+pub mod table;
+pub mod tblfm;
 pub mod text_size;
 pub mod timestamp;
+pub mod var;
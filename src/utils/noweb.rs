@@ -0,0 +1,610 @@
+use std::collections::HashMap;
+
+use lsp_types::Url;
+use orgize::{ast::SourceBlock, rowan::ast::AstNode, Org, SyntaxNode};
+
+use crate::backend::{Backend, Documents};
+use crate::utils::eval::{ensure_trusted, EvalPolicy};
+use crate::utils::language::{language_config, Executor};
+use crate::utils::library::library_blocks;
+use crate::utils::src_block::{
+    block_name, collect_src_blocks, header_argument, property_drawer, property_keyword,
+};
+use crate::utils::var::{
+    assign_vars, call_key, collect_named_data, parse_assignments, parse_vars, Var, VarValue,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NowebContext {
+    Tangle,
+    Eval,
+}
+
+/// Owned copy of a source block, so it can be kept across `.await`
+#[derive(Clone, Debug)]
+pub struct NowebBlock {
+    pub name: Option<String>,
+    pub noweb_ref: Option<String>,
+    pub language: String,
+    pub executor: Option<Executor>,
+    pub noweb: String,
+    pub vars: Vec<(String, Var)>,
+    pub eval: EvalPolicy,
+    pub content: String,
+}
+
+impl NowebBlock {
+    pub fn new(block: &SourceBlock, documents: &Documents) -> Self {
+        NowebBlock::from_parts(
+            block.syntax(),
+            block_name(block.syntax()),
+            &block.language().unwrap_or_default(),
+            &block.parameters().unwrap_or_default(),
+            block.value(),
+            documents,
+        )
+    }
+
+    /// Creates from inline source or called blocks, `node` is used for
+    /// looking up property drawers and keywords
+    pub fn from_parts(
+        node: &SyntaxNode,
+        name: Option<String>,
+        language: &str,
+        parameters: &str,
+        content: String,
+        documents: &Documents,
+    ) -> Self {
+        let arg1 = parameters;
+        let arg2 = property_drawer(node, language);
+        let arg3 = property_keyword(node, language);
+
+        let noweb_ref = header_argument(arg1, &arg2, &arg3, ":noweb-ref", "");
+
+        NowebBlock {
+            name,
+            noweb_ref: (!noweb_ref.is_empty()).then(|| noweb_ref.to_string()),
+            executor: language_config(documents, language, node).executor(),
+            language: language.to_string(),
+            noweb: header_argument(arg1, &arg2, &arg3, ":noweb", "no").to_string(),
+            vars: parse_vars(&[&arg3, &arg2, arg1]),
+            eval: EvalPolicy::parse(header_argument(arg1, &arg2, &arg3, ":eval", "yes")),
+            content,
+        }
+    }
+
+    /// Overrides `:var` bindings, e.g. by arguments of `#+CALL:` or `block(n=2)`
+    pub fn override_vars(&mut self, vars: Vec<(String, Var)>) {
+        for (name, var) in vars {
+            match self.vars.iter_mut().find(|(n, _)| *n == name) {
+                Some(existing) => existing.1 = var,
+                None => self.vars.push((name, var)),
+            }
+        }
+    }
+
+    /// Returns true if noweb references in this block should be expanded in given context
+    pub fn enabled(&self, ctx: NowebContext) -> bool {
+        match self.noweb.as_str() {
+            "yes" | "no-export" | "strip-export" => true,
+            "tangle" => ctx == NowebContext::Tangle,
+            "eval" => ctx == NowebContext::Eval,
+            _ => false,
+        }
+    }
+}
+
+/// Named source blocks, tables and lists of a document, used to expand
+/// `<<name>>` and `<<name()>>` and resolve `:var`
+pub struct Noweb {
+    url: Url,
+    /// whether executing blocks of this document was allowed
+    trusted: bool,
+    blocks: Vec<NowebBlock>,
+    /// named tables and lists
+    data: HashMap<String, VarValue>,
+    /// outputs of evaluated blocks, keyed by `call_key`
+    results: HashMap<String, String>,
+}
+
+/// Block to be evaluated for `<<name()>>` or `:var x=name()`
+pub struct Evaluation {
+    /// block name with arguments, see `call_key`
+    pub key: String,
+    pub name: String,
+    /// overrides `:var` of the called block
+    pub args: Vec<(String, Var)>,
+}
+
+impl Noweb {
+    pub fn new(url: &Url, org: &Org, documents: &Documents) -> Self {
+        let mut blocks: Vec<_> = collect_src_blocks(org)
+            .iter()
+            .map(|block| NowebBlock::new(block, documents))
+            .filter(|block| block.name.is_some() || block.noweb_ref.is_some())
+            .collect();
+
+        // local blocks take precedence over library ones with the same name
+        blocks.extend(library_blocks(documents));
+
+        Noweb {
+            url: url.clone(),
+            trusted: false,
+            blocks,
+            data: collect_named_data(org.document().syntax()),
+            results: HashMap::new(),
+        }
+    }
+
+    /// Fails unless the document is trusted or user confirms executing it,
+    /// user is asked at most once
+    pub async fn ensure_trusted<B: Backend>(&mut self, backend: &B) -> anyhow::Result<()> {
+        if !self.trusted {
+            ensure_trusted(backend, &self.url).await?;
+            self.trusted = true;
+        }
+
+        Ok(())
+    }
+
+    /// Document containing the blocks
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Blocks referenced as `<<name()>>` or `:var x=name()` which need to be
+    /// evaluated before expanding given block, dependencies come first and
+    /// already evaluated ones are skipped
+    pub fn calls(&self, block: &NowebBlock, ctx: NowebContext) -> anyhow::Result<Vec<Evaluation>> {
+        let mut calls = vec![];
+        self.collect_calls(block, ctx, &mut root_stack(block), &mut calls)?;
+        Ok(calls)
+    }
+
+    /// Keeps the output of an evaluated block, used for expanding references to it
+    pub fn insert_result(&mut self, key: String, output: String) {
+        self.results.insert(key, output);
+    }
+
+    /// Expands the content of given block, variable assignments are prepended
+    /// when evaluating
+    ///
+    /// Blocks returned by `calls` must be evaluated first, otherwise their
+    /// references are kept as it is and variables bound to them can't be resolved.
+    pub fn expand(&self, block: &NowebBlock, ctx: NowebContext) -> anyhow::Result<String> {
+        let content = self.expand_content(block, ctx, &mut root_stack(block))?;
+
+        if ctx == NowebContext::Eval {
+            Ok(self.prologue(block)? + &content)
+        } else {
+            Ok(content)
+        }
+    }
+
+    /// Variable assignments of given block
+    fn prologue(&self, block: &NowebBlock) -> anyhow::Result<String> {
+        if block.vars.is_empty() {
+            return Ok(String::new());
+        }
+
+        let mut vars = Vec::with_capacity(block.vars.len());
+
+        for (name, var) in &block.vars {
+            let value = match var {
+                Var::Literal(value) => value.clone(),
+                Var::Reference(reference) => {
+                    if let Some(output) = self.results.get(reference) {
+                        VarValue::from_output(output)
+                    } else if let Some(value) = self.data.get(reference) {
+                        value.clone()
+                    } else {
+                        anyhow::bail!("cannot resolve {reference:?} for variable {name:?}");
+                    }
+                }
+                Var::Call { name: callee, args } => {
+                    let key = call_key(callee, args);
+                    let Some(output) = self.results.get(&key) else {
+                        anyhow::bail!("cannot resolve {key:?} for variable {name:?}");
+                    };
+                    VarValue::from_output(output)
+                }
+            };
+
+            vars.push((name.clone(), value));
+        }
+
+        assign_vars(&block.language, &vars)
+    }
+
+    fn named(&self, name: &str) -> Option<&NowebBlock> {
+        self.blocks.iter().find(|b| b.name.as_deref() == Some(name))
+    }
+
+    /// A block with `#+NAME:` takes precedence, otherwise all blocks
+    /// sharing the `:noweb-ref` in document order
+    fn find(&self, name: &str) -> Vec<&NowebBlock> {
+        if let Some(block) = self.blocks.iter().find(|b| b.name.as_deref() == Some(name)) {
+            return vec![block];
+        }
+
+        self.blocks
+            .iter()
+            .filter(|b| b.noweb_ref.as_deref() == Some(name))
+            .collect()
+    }
+
+    /// Collects blocks need to be evaluated, dependencies come first
+    fn collect_calls(
+        &self,
+        block: &NowebBlock,
+        ctx: NowebContext,
+        stack: &mut Vec<String>,
+        calls: &mut Vec<Evaluation>,
+    ) -> anyhow::Result<()> {
+        // variables are only assigned when evaluating
+        if ctx == NowebContext::Eval {
+            for (_, var) in &block.vars {
+                let (name, args) = match var {
+                    Var::Reference(name) => (name, vec![]),
+                    Var::Call { name, args } => (name, args.clone()),
+                    Var::Literal(_) => continue,
+                };
+
+                if let Some(callee) = self.named(name) {
+                    self.collect_call(name, callee, args, stack, calls)?;
+                }
+            }
+        }
+
+        if !block.enabled(ctx) {
+            return Ok(());
+        }
+
+        for line in block.content.lines() {
+            let mut line = line;
+
+            while let Some((_, reference, suffix)) = parse_reference(line) {
+                line = suffix;
+
+                match reference {
+                    Reference::Call(name, args) => {
+                        if let Some(callee) = self.find(name).first() {
+                            let args = parse_assignments(args);
+                            self.collect_call(name, callee, args, stack, calls)?;
+                        }
+                    }
+                    Reference::Block(name) => {
+                        let blocks = self.find(name);
+
+                        if blocks.is_empty() {
+                            continue;
+                        }
+
+                        check_cycle(stack, name)?;
+
+                        stack.push(name.to_string());
+                        for child in blocks {
+                            self.collect_calls(child, ctx, stack, calls)?;
+                        }
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn collect_call(
+        &self,
+        name: &str,
+        callee: &NowebBlock,
+        args: Vec<(String, Var)>,
+        stack: &mut Vec<String>,
+        calls: &mut Vec<Evaluation>,
+    ) -> anyhow::Result<()> {
+        let key = call_key(name, &args);
+
+        if self.results.contains_key(&key) || calls.iter().any(|c| c.key == key) {
+            return Ok(());
+        }
+
+        check_cycle(stack, name)?;
+
+        let mut block = callee.clone();
+        block.override_vars(args.clone());
+
+        // arguments may call other blocks as well
+        stack.push(name.to_string());
+        self.collect_calls(&block, NowebContext::Eval, stack, calls)?;
+        stack.pop();
+
+        calls.push(Evaluation {
+            key,
+            name: name.to_string(),
+            args,
+        });
+
+        Ok(())
+    }
+
+    fn expand_content(
+        &self,
+        block: &NowebBlock,
+        ctx: NowebContext,
+        stack: &mut Vec<String>,
+    ) -> anyhow::Result<String> {
+        if !block.enabled(ctx) {
+            return Ok(block.content.clone());
+        }
+
+        let mut output = String::with_capacity(block.content.len());
+
+        for line in block.content.split_inclusive('\n') {
+            self.expand_line(line, ctx, stack, &mut output)?;
+        }
+
+        Ok(output)
+    }
+
+    fn expand_line(
+        &self,
+        line: &str,
+        ctx: NowebContext,
+        stack: &mut Vec<String>,
+        output: &mut String,
+    ) -> anyhow::Result<()> {
+        let Some((prefix, reference, suffix)) = parse_reference(line) else {
+            output.push_str(line);
+            return Ok(());
+        };
+
+        let expanded = match reference {
+            Reference::Call(name, args) => self
+                .results
+                .get(&call_key(name, &parse_assignments(args)))
+                .cloned(),
+            Reference::Block(name) => {
+                let blocks = self.find(name);
+
+                if blocks.is_empty() {
+                    None
+                } else {
+                    check_cycle(stack, name)?;
+
+                    stack.push(name.to_string());
+                    let mut bodies = Vec::with_capacity(blocks.len());
+                    for block in blocks {
+                        bodies.push(self.expand_content(block, ctx, stack)?);
+                    }
+                    stack.pop();
+
+                    Some(
+                        bodies
+                            .iter()
+                            .map(|body| body.trim_end_matches('\n'))
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    )
+                }
+            }
+        };
+
+        let Some(expanded) = expanded else {
+            // unresolved references are kept as it is
+            let len = line.len() - suffix.len();
+            output.push_str(&line[0..len]);
+            return self.expand_line(suffix, ctx, stack, output);
+        };
+
+        // prefix is repeated on every expanded line
+        let is_indent = prefix.trim().is_empty();
+        for (i, expanded_line) in expanded.trim_end_matches('\n').lines().enumerate() {
+            if i > 0 {
+                output.push('\n');
+            }
+            if !(is_indent && expanded_line.is_empty()) {
+                output.push_str(prefix);
+            }
+            output.push_str(expanded_line);
+        }
+
+        self.expand_line(suffix, ctx, stack, output)
+    }
+}
+
+fn root_stack(block: &NowebBlock) -> Vec<String> {
+    block.name.iter().chain(&block.noweb_ref).cloned().collect()
+}
+
+fn check_cycle(stack: &[String], name: &str) -> anyhow::Result<()> {
+    if stack.iter().any(|n| n == name) {
+        anyhow::bail!(
+            "noweb reference cycle detected: {} -> {name}",
+            stack.join(" -> ")
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Reference<'a> {
+    /// `<<name>>`
+    Block(&'a str),
+    /// `<<name()>>` or `<<name(n=2)>>`, with arguments
+    Call(&'a str, &'a str),
+}
+
+/// Finds the first noweb reference in line, returns (prefix, reference, suffix)
+fn parse_reference(line: &str) -> Option<(&str, Reference, &str)> {
+    let mut offset = 0;
+
+    while let Some(i) = line[offset..].find(">>") {
+        let end = offset + i;
+        offset = end + 2;
+
+        let Some(start) = line[0..end].rfind("<<") else {
+            continue;
+        };
+
+        let inner = &line[start + 2..end];
+
+        let reference = match inner.split_once('(') {
+            Some((name, args)) if args.ends_with(')') => {
+                Reference::Call(name, &args[..args.len() - 1])
+            }
+            Some(_) => continue,
+            None => Reference::Block(inner),
+        };
+
+        let (Reference::Call(name, _) | Reference::Block(name)) = reference;
+
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '<') {
+            continue;
+        }
+
+        return Some((&line[0..start], reference, &line[end + 2..]));
+    }
+
+    None
+}
+
+#[test]
+fn parse() {
+    assert_eq!(
+        parse_reference("  <<a>>\n"),
+        Some(("  ", Reference::Block("a"), "\n"))
+    );
+    assert_eq!(
+        parse_reference("x = <<b()>>;"),
+        Some(("x = ", Reference::Call("b", ""), ";"))
+    );
+    assert_eq!(
+        parse_reference("cout << a >> b; <<c>>"),
+        Some(("cout << a >> b; ", Reference::Block("c"), ""))
+    );
+    assert_eq!(parse_reference("a << b >> c"), None);
+}
+
+#[test]
+fn test() {
+    let documents = Documents::default();
+    let url = Url::parse("test://test.org").unwrap();
+
+    let org = Org::parse(
+        r#"#+begin_src sh :noweb yes
+main() {
+  <<body>>
+}
+# <<comment>>
+#+end_src
+
+#+begin_src sh :noweb-ref body
+echo 1
+
+echo 2
+#+end_src
+
+#+begin_src sh :noweb-ref body
+echo 3
+#+end_src
+
+#+name: comment
+#+begin_src sh
+line 1
+line 2
+#+end_src
+
+#+name: cycle
+#+begin_src sh :noweb tangle
+<<cycle>>
+#+end_src
+"#,
+    );
+
+    let blocks = collect_src_blocks(&org);
+    let noweb = Noweb::new(&url, &org, &documents);
+
+    let block = NowebBlock::new(&blocks[0], &documents);
+    assert_eq!(
+        noweb.expand(&block, NowebContext::Tangle).unwrap(),
+        r#"main() {
+  echo 1
+
+  echo 2
+  echo 3
+}
+# line 1
+# line 2
+"#
+    );
+
+    let block = NowebBlock::new(&blocks[4], &documents);
+    assert!(noweb.expand(&block, NowebContext::Tangle).is_err());
+    assert_eq!(
+        noweb.expand(&block, NowebContext::Eval).unwrap(),
+        "<<cycle>>\n"
+    );
+}
+
+#[test]
+fn test_vars() {
+    let documents = Documents::default();
+    let url = Url::parse("test://test.org").unwrap();
+
+    let org = Org::parse(
+        r#"#+name: tbl
+| a | 1 |
+|---+---|
+| b | 2 |
+
+#+begin_src python :var t=tbl :var n=2
+print(t)
+#+end_src
+
+#+begin_src python :var x=missing
+#+end_src
+
+#+name: square
+#+begin_src python :var n=1 :results value
+return n * n
+#+end_src
+
+#+begin_src python :var a=square(n=2) :var b=square(n=3)
+print(a, b)
+#+end_src
+"#,
+    );
+
+    let blocks = collect_src_blocks(&org);
+    let noweb = Noweb::new(&url, &org, &documents);
+
+    let block = NowebBlock::new(&blocks[0], &documents);
+    assert_eq!(
+        noweb.expand(&block, NowebContext::Eval).unwrap(),
+        "t = [[\"a\",1],[\"b\",2]]\nn = 2\nprint(t)\n"
+    );
+    assert_eq!(
+        noweb.expand(&block, NowebContext::Tangle).unwrap(),
+        "print(t)\n"
+    );
+
+    let block = NowebBlock::new(&blocks[1], &documents);
+    assert!(noweb.expand(&block, NowebContext::Eval).is_err());
+
+    // each set of arguments is evaluated separately
+    let block = NowebBlock::new(&blocks[3], &documents);
+    let calls = noweb.calls(&block, NowebContext::Eval).unwrap();
+    assert_eq!(
+        calls.iter().map(|c| c.key.as_str()).collect::<Vec<_>>(),
+        vec!["square(n=2)", "square(n=3)"]
+    );
+    assert_eq!(
+        calls[1].args,
+        vec![("n".into(), Var::Literal(VarValue::Number("3".into())))]
+    );
+
+    // unevaluated calls can't be resolved
+    assert!(noweb.expand(&block, NowebContext::Eval).is_err());
+}
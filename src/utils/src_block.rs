@@ -1,11 +1,5 @@
-use jetscii::Substring;
-use nom::{
-    bytes::complete::take_while1,
-    character::complete::{space0, space1},
-    InputTake,
-};
 use orgize::{
-    ast::{Headline, Keyword, SourceBlock, Token},
+    ast::{AffiliatedKeyword, Headline, Keyword, SourceBlock},
     export::{from_fn_with_ctx, Container, Event},
     rowan::ast::AstNode,
     Org, SyntaxKind, SyntaxNode,
@@ -38,29 +32,10 @@ pub fn collect_src_blocks(org: &Org) -> Vec<SourceBlock> {
     blocks
 }
 
-pub fn language_comments(language: &str) -> Option<(&str, &str)> {
-    match language {
-        "c" | "cpp" | "c++" | "go" | "js" | "javascript" | "ts" | "typescript" | "rust"
-        | "vera" | "jsonc" => Some(("//", "")),
-        "toml" | "tml" | "yaml" | "yml" | "conf" | "gitconfig" | "conf-toml" | "sh" | "shell"
-        | "bash" | "zsh" | "fish" => Some(("#", "")),
-        "lua" | "sql" => Some(("--", "")),
-        "lisp" | "emacs-lisp" | "elisp" => Some((";;", "")),
-        "xml" | "html" | "svg" => Some(("<!--", "-->")),
-        _ => None,
-    }
-}
-
-pub fn language_execute_command(language: &str) -> Option<&str> {
-    match language {
-        "js" | "javascript" => Some("node"),
-        "sh" | "bash" => Some("bash"),
-        "py" | "python" => Some("python"),
-        "fish" => Some("fish"),
-        _ => None,
-    }
-}
-
+/// Returns the value of header argument `key`, `arg1` takes precedence over
+/// `arg2` and `arg2` over `arg3`
+///
+/// Within one argument string, later occurrences override earlier ones.
 pub fn header_argument<'a>(
     arg1: &'a str,
     arg2: &'a str,
@@ -68,95 +43,288 @@ pub fn header_argument<'a>(
     key: &str,
     default: &'static str,
 ) -> &'a str {
-    extract_header_args(arg1, key)
-        .or_else(|_| extract_header_args(arg2, key))
-        .or_else(|_| extract_header_args(arg3, key))
+    [arg1, arg2, arg3]
+        .into_iter()
+        .find_map(|args| find_header_argument(args, key))
         .unwrap_or(default)
 }
 
-pub fn property_keyword(node: &SyntaxNode) -> Option<Token> {
-    node.ancestors()
+/// Returns the value of the last `key` in header arguments
+pub fn find_header_argument<'a>(args: &'a str, key: &str) -> Option<&'a str> {
+    split_header_args(args)
+        .into_iter()
+        .rev()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value)
+}
+
+/// Header arguments from `#+PROPERTY: header-args` followed by
+/// `#+PROPERTY: header-args:{language}` of the document containing `node`
+pub fn property_keyword(node: &SyntaxNode, language: &str) -> String {
+    let (general, specific) = keyword_header_args(node, language);
+    join_args(general, specific)
+}
+
+/// Returns `#+PROPERTY: header-args:{language}` of the document containing `node`
+pub fn property_keyword_language(node: &SyntaxNode, language: &str) -> Option<String> {
+    let (_, specific) = keyword_header_args(node, language);
+    (!specific.is_empty()).then_some(specific)
+}
+
+fn keyword_header_args(node: &SyntaxNode, language: &str) -> (String, String) {
+    let (mut general, mut specific) = (String::new(), String::new());
+
+    let Some(section) = node
+        .ancestors()
         .find(|n| n.kind() == SyntaxKind::DOCUMENT)
         .and_then(|n| n.first_child())
         .filter(|n| n.kind() == SyntaxKind::SECTION)
-        .and_then(|n| {
-            n.children()
-                .filter_map(Keyword::cast)
-                .filter(|kw| kw.key().eq_ignore_ascii_case("PROPERTY"))
-                .map(|kw| kw.value())
-                .find(|value| value.trim_start().starts_with("header-args "))
-        })
-}
+    else {
+        return (general, specific);
+    };
+
+    for keyword in section
+        .children()
+        .filter_map(Keyword::cast)
+        .filter(|kw| kw.key().eq_ignore_ascii_case("PROPERTY"))
+    {
+        let value = keyword.value();
+        let value = value.trim_start();
+        let (name, args) = value
+            .split_once(|c: char| c.is_whitespace())
+            .unwrap_or((value, ""));
+        let (name, append) = match name.strip_suffix('+') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
 
-pub fn property_drawer(node: &SyntaxNode) -> Option<Token> {
-    node.ancestors()
-        .find_map(Headline::cast)
-        .and_then(|hdl| hdl.properties())
-        .and_then(|drawer| drawer.get("header-args"))
+        if name == "header-args" {
+            accumulate(&mut general, args, append);
+        } else if !language.is_empty() && name.strip_prefix("header-args:") == Some(language) {
+            accumulate(&mut specific, args, append);
+        }
+    }
+
+    (general, specific)
 }
 
-pub fn extract_header_args<'a>(input: &'a str, key: &str) -> Result<&'a str, nom::Err<()>> {
-    let mut i = input;
+/// Header arguments inherited from property drawers of ancestor headlines,
+/// `header-args` followed by `header-args:{language}`
+///
+/// Properties of inner headlines replace inherited ones, unless they end with
+/// `+`, e.g. `:header-args+:`, which appends to them instead.
+pub fn property_drawer(node: &SyntaxNode, language: &str) -> String {
+    let (mut general, mut specific) = (String::new(), String::new());
+
+    let mut headlines: Vec<_> = node.ancestors().filter_map(Headline::cast).collect();
+    headlines.reverse();
 
-    while !i.is_empty() {
-        let (input, _) = space0(i)?;
-        let (input, name) = take_while1(|c| c != ' ' && c != '\t')(input)?;
+    let language_key = format!("header-args:{language}");
 
-        if !name.eq_ignore_ascii_case(key) {
-            debug_assert!(input.len() < i.len(), "{} < {}", input.len(), i.len());
-            i = input;
+    for drawer in headlines.iter().filter_map(|hdl| hdl.properties()) {
+        if let Some(args) = drawer.get("header-args") {
+            accumulate(&mut general, &args, false);
+        }
+        if let Some(args) = drawer.get("header-args+") {
+            accumulate(&mut general, &args, true);
+        }
+
+        if language.is_empty() {
             continue;
         }
+        if let Some(args) = drawer.get(&language_key) {
+            accumulate(&mut specific, &args, false);
+        }
+        if let Some(args) = drawer.get(&format!("{language_key}+")) {
+            accumulate(&mut specific, &args, true);
+        }
+    }
+
+    join_args(general, specific)
+}
+
+fn accumulate(value: &mut String, args: &str, append: bool) {
+    if !append {
+        value.clear();
+    }
+
+    let args = args.trim();
+    if !value.is_empty() && !args.is_empty() {
+        value.push(' ');
+    }
+    value.push_str(args);
+}
 
-        let (input, _) = space1(input)?;
+fn join_args(general: String, specific: String) -> String {
+    match (general.is_empty(), specific.is_empty()) {
+        (_, true) => general,
+        (true, false) => specific,
+        (false, false) => format!("{general} {specific}"),
+    }
+}
 
-        if let Some(idx) = Substring::new(" :")
-            .find(input)
-            .or_else(|| Substring::new("\t:").find(input))
-        {
-            let idx = input[0..idx]
-                .rfind(|c| c != ' ' && c != '\t')
-                .map(|i| i + 1)
-                .unwrap_or(idx);
+/// Returns the value of `#+NAME:` affiliated keyword
+pub fn block_name(node: &SyntaxNode) -> Option<String> {
+    node.children()
+        .filter_map(AffiliatedKeyword::cast)
+        .filter(|k| k.key().eq_ignore_ascii_case("NAME"))
+        .find_map(|k| {
+            let raw = k.syntax().to_string();
+            let (_, value) = raw.split_once(':')?;
+            let value = value.trim();
+            (!value.is_empty()).then(|| value.to_string())
+        })
+}
 
-            let (_, value) = input.take_split(idx);
+/// Splits header arguments into `(key, value)` pairs in order of appearance
+///
+/// Keys are words starting with `:`, colons inside quotes don't start a new
+/// key, e.g. `:var x="a :b"` is a single argument.
+pub fn split_header_args(input: &str) -> Vec<(&str, &str)> {
+    let mut keys = vec![];
+    let mut in_quote = false;
+    let mut prev = ' ';
 
-            return Ok(value.trim());
-        } else {
-            return Ok(input.trim());
+    for (i, c) in input.char_indices() {
+        if c == '"' {
+            in_quote = !in_quote;
+        } else if c == ':' && !in_quote && prev.is_whitespace() {
+            let end = input[i..]
+                .find(char::is_whitespace)
+                .map_or(input.len(), |len| i + len);
+            keys.push((i, end));
         }
+        prev = c;
     }
 
-    Err(nom::Err::Error(()))
+    keys.iter()
+        .enumerate()
+        .map(|(n, &(start, end))| {
+            let next = keys.get(n + 1).map_or(input.len(), |(start, _)| *start);
+            (&input[start..end], input[end..next].trim())
+        })
+        .collect()
 }
 
 #[test]
 fn parse_header_args() {
-    assert!(extract_header_args("", ":tangle").is_err());
-    assert!(extract_header_args(" :noweb yes", ":tangle1").is_err());
-    assert!(extract_header_args(":tangle", ":tangle").is_err());
+    assert_eq!(find_header_argument("", ":tangle"), None);
+    assert_eq!(find_header_argument(" :noweb yes", ":tangle1"), None);
+    assert_eq!(find_header_argument(":tangle", ":tangle"), Some(""));
 
-    assert_eq!(extract_header_args(":tangle  ", ":tangle").unwrap(), "");
+    assert_eq!(find_header_argument(":tangle  ", ":tangle"), Some(""));
 
     assert_eq!(
-        extract_header_args(":tangle emacs.d/init.el", ":tangle").unwrap(),
+        find_header_argument(":tangle emacs.d/init.el", ":tangle").unwrap(),
         "emacs.d/init.el"
     );
     assert_eq!(
-        extract_header_args(" :tangle emacs.d/init.el", ":tangle").unwrap(),
+        find_header_argument(" :tangle emacs.d/init.el", ":tangle").unwrap(),
         "emacs.d/init.el"
     );
     assert_eq!(
-        extract_header_args(" :tangle emacs.d/init.el  :noweb yes", ":tangle").unwrap(),
+        find_header_argument(" :tangle emacs.d/init.el  :noweb yes", ":tangle").unwrap(),
         "emacs.d/init.el"
     );
     assert_eq!(
-        extract_header_args(" :noweb yes :tangle emacs.d/init.el", ":tangle").unwrap(),
+        find_header_argument(" :noweb yes :tangle emacs.d/init.el", ":tangle").unwrap(),
         "emacs.d/init.el"
     );
 
     assert_eq!(
-        extract_header_args(":results output code", ":results").unwrap(),
+        find_header_argument(":results output code", ":results").unwrap(),
         "output code"
     );
+
+    assert_eq!(
+        header_argument(":results output :results value", "", "", ":results", ""),
+        "value"
+    );
+    assert_eq!(
+        header_argument(
+            r#":results output :var s="x :results silent""#,
+            "",
+            ":results value",
+            ":results",
+            ""
+        ),
+        "output"
+    );
+    assert_eq!(
+        header_argument(r#":var s="x :results silent""#, "", "", ":results", "value"),
+        "value"
+    );
+    assert_eq!(
+        header_argument(
+            r#":prologue "echo a :b" :dir /tmp"#,
+            "",
+            "",
+            ":prologue",
+            ""
+        ),
+        r#""echo a :b""#
+    );
+
+    assert_eq!(
+        split_header_args(r#" :var x="a :b", y=1 :results  output code :tangle"#),
+        vec![
+            (":var", r#"x="a :b", y=1"#),
+            (":results", "output code"),
+            (":tangle", "")
+        ]
+    );
+    assert_eq!(split_header_args("a:b"), vec![]);
+}
+
+#[test]
+fn inherited_header_args() {
+    let org = Org::parse(
+        r#"#+PROPERTY: header-args :results output :session a
+#+PROPERTY: header-args+ :var x=1
+#+PROPERTY: header-args:python :session py
+
+* a
+:PROPERTIES:
+:header-args: :dir /tmp
+:header-args:python+: :var y=2
+:END:
+** b
+:PROPERTIES:
+:header-args+: :cache yes
+:END:
+#+begin_src python
+#+end_src
+** c
+:PROPERTIES:
+:header-args: :cache no
+:END:
+#+begin_src sh
+#+end_src
+"#,
+    );
+
+    let blocks = collect_src_blocks(&org);
+
+    let keyword = property_keyword(blocks[0].syntax(), "python");
+    assert_eq!(keyword, ":results output :session a :var x=1 :session py");
+    assert_eq!(header_argument("", "", &keyword, ":session", "none"), "py");
+
+    let drawer = property_drawer(blocks[0].syntax(), "python");
+    assert_eq!(drawer, ":dir /tmp :cache yes :var y=2");
+    assert_eq!(
+        header_argument("", &drawer, &keyword, ":cache", "no"),
+        "yes"
+    );
+    assert_eq!(
+        header_argument(":cache no", &drawer, &keyword, ":cache", "no"),
+        "no"
+    );
+    assert_eq!(header_argument("", &drawer, &keyword, ":dir", ""), "/tmp");
+
+    let drawer = property_drawer(blocks[1].syntax(), "sh");
+    assert_eq!(drawer, ":cache no");
+    assert_eq!(
+        property_keyword(blocks[1].syntax(), "sh"),
+        ":results output :session a :var x=1"
+    );
 }
@@ -0,0 +1,629 @@
+use std::fmt::Write;
+
+use chrono::NaiveDateTime;
+use orgize::{
+    rowan::{TextRange, TextSize},
+    SyntaxKind, SyntaxNode,
+};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use unicode_width::UnicodeWidthStr;
+
+/// Rows of an org table, cells are trimmed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub indent: String,
+    pub rows: Vec<TableRow>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableRow {
+    /// `|---+---|`
+    Rule,
+    Cells(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// Alignment and width set by `<l>`, `<r10>` or `<8>` cookies
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Cookie {
+    pub align: Option<Align>,
+    /// width columns are shrunk to when folded, never used for padding
+    pub width: Option<usize>,
+}
+
+impl Table {
+    /// Parses table lines, returns `None` if any line isn't a table row
+    pub fn parse(text: &str) -> Option<Table> {
+        let mut table = Table::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            let trimmed = line.trim_start();
+
+            if i == 0 {
+                table.indent = line[..line.len() - trimmed.len()].to_string();
+            }
+
+            let row = trimmed.strip_prefix('|')?;
+
+            if row.starts_with('-') {
+                table.rows.push(TableRow::Rule);
+                continue;
+            }
+
+            let row = row.strip_suffix('|').unwrap_or(row);
+
+            table.rows.push(TableRow::Cells(
+                row.split('|').map(|cell| cell.trim().to_string()).collect(),
+            ));
+        }
+
+        Some(table)
+    }
+
+    pub fn columns(&self) -> usize {
+        self.rows
+            .iter()
+            .map(|row| match row {
+                TableRow::Rule => 0,
+                TableRow::Cells(cells) => cells.len(),
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Rows with cells only, excluding rules
+    pub fn data_rows(&self) -> impl Iterator<Item = &Vec<String>> {
+        self.rows.iter().filter_map(|row| match row {
+            TableRow::Rule => None,
+            TableRow::Cells(cells) => Some(cells),
+        })
+    }
+
+    /// Cookies of each column, taken from rows consisting of cookies only
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let mut cookies = vec![Cookie::default(); self.columns()];
+
+        for cells in self.data_rows().filter(|cells| is_cookie_row(cells)) {
+            for (cookie, cell) in cookies.iter_mut().zip(cells) {
+                if let Some(parsed) = parse_cookie(cell) {
+                    *cookie = parsed;
+                }
+            }
+        }
+
+        cookies
+    }
+
+    /// Renders the table with cells padded to the width of their columns,
+    /// every line ends with a newline
+    ///
+    /// Width cookies don't affect rendering, in org mode they only shrink
+    /// columns visually and cells keep their full content
+    pub fn render(&self) -> String {
+        let columns = self.columns().max(1);
+        let cookies = self.cookies();
+
+        let mut widths = vec![1; columns];
+        for cells in self.data_rows() {
+            for (width, cell) in widths.iter_mut().zip(cells) {
+                *width = (*width).max(cell.width());
+            }
+        }
+
+        let aligns: Vec<_> = (0..columns)
+            .map(|column| {
+                cookies
+                    .get(column)
+                    .and_then(|cookie| cookie.align)
+                    .unwrap_or_else(|| self.default_align(column))
+            })
+            .collect();
+
+        let mut output = String::new();
+
+        for row in &self.rows {
+            output.push_str(&self.indent);
+
+            match row {
+                TableRow::Rule => {
+                    output.push('|');
+                    for (column, width) in widths.iter().enumerate() {
+                        if column > 0 {
+                            output.push('+');
+                        }
+                        output.push_str(&"-".repeat(width + 2));
+                    }
+                    output.push('|');
+                }
+                TableRow::Cells(cells) => {
+                    output.push('|');
+                    for (column, width) in widths.iter().enumerate() {
+                        let cell = cells.get(column).map(String::as_str).unwrap_or_default();
+                        let padding = width.saturating_sub(cell.width());
+
+                        let (left, right) = match aligns[column] {
+                            _ if is_cookie_row(cells) => (0, padding),
+                            Align::Left => (0, padding),
+                            Align::Right => (padding, 0),
+                            Align::Center => (padding / 2, padding - padding / 2),
+                        };
+
+                        let _ = write!(
+                            &mut output,
+                            " {}{cell}{} |",
+                            " ".repeat(left),
+                            " ".repeat(right)
+                        );
+                    }
+                }
+            }
+
+            output.push('\n');
+        }
+
+        output
+    }
+
+    /// Creates a table from rows of cells, `|` in cells is escaped
+    pub fn from_cells(rows: Vec<Vec<String>>) -> Table {
+        Table {
+            indent: String::new(),
+            rows: rows
+                .into_iter()
+                .map(|cells| {
+                    TableRow::Cells(
+                        cells
+                            .into_iter()
+                            .map(|cell| cell.replace('|', "\\vert{}"))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Pads rows with empty cells, so every row has the same number of cells
+    pub fn normalize(&mut self) {
+        let columns = self.columns();
+        for row in &mut self.rows {
+            if let TableRow::Cells(cells) = row {
+                cells.resize(columns, String::new());
+            }
+        }
+    }
+
+    /// Inserts an empty row before `index`
+    pub fn insert_row(&mut self, index: usize) {
+        let columns = self.columns();
+        self.rows.insert(
+            index.min(self.rows.len()),
+            TableRow::Cells(vec![String::new(); columns]),
+        );
+    }
+
+    /// Inserts an empty column before `index`
+    pub fn insert_column(&mut self, index: usize) {
+        self.normalize();
+        for row in &mut self.rows {
+            if let TableRow::Cells(cells) = row {
+                cells.insert(index.min(cells.len()), String::new());
+            }
+        }
+    }
+
+    pub fn remove_column(&mut self, index: usize) {
+        self.normalize();
+        for row in &mut self.rows {
+            if let TableRow::Cells(cells) = row {
+                if index < cells.len() {
+                    cells.remove(index);
+                }
+            }
+        }
+    }
+
+    pub fn swap_columns(&mut self, a: usize, b: usize) {
+        self.normalize();
+        for row in &mut self.rows {
+            if let TableRow::Cells(cells) = row {
+                if a < cells.len() && b < cells.len() {
+                    cells.swap(a, b);
+                }
+            }
+        }
+    }
+
+    /// Sorts rows between the rules surrounding `row` by given column
+    pub fn sort(&mut self, row: usize, column: usize, kind: SortKind, descending: bool) {
+        let is_rule = |row: &TableRow| *row == TableRow::Rule;
+
+        let start = self.rows[..row.min(self.rows.len())]
+            .iter()
+            .rposition(is_rule)
+            .map_or(0, |index| index + 1);
+        let end = self.rows[start..]
+            .iter()
+            .position(is_rule)
+            .map_or(self.rows.len(), |index| start + index);
+
+        fn cell(row: &TableRow, column: usize) -> &str {
+            match row {
+                TableRow::Cells(cells) => cells.get(column).map(String::as_str).unwrap_or_default(),
+                TableRow::Rule => "",
+            }
+        }
+
+        self.rows[start..end].sort_by(|a, b| {
+            let ordering = kind.compare(cell(a, column), cell(b, column));
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    /// Swaps rows and columns, rules are dropped
+    pub fn transpose(&mut self) {
+        let rows: Vec<_> = self.data_rows().cloned().collect();
+        let columns = self.columns();
+
+        self.rows = (0..columns)
+            .map(|column| {
+                TableRow::Cells(
+                    rows.iter()
+                        .map(|cells| cells.get(column).cloned().unwrap_or_default())
+                        .collect(),
+                )
+            })
+            .collect();
+    }
+
+    /// Columns consisting mostly of numbers are right aligned
+    fn default_align(&self, column: usize) -> Align {
+        let (mut numbers, mut total) = (0, 0);
+
+        for cells in self.data_rows().filter(|cells| !is_cookie_row(cells)) {
+            match cells.get(column) {
+                Some(cell) if !cell.is_empty() => {
+                    total += 1;
+                    if is_number(cell) {
+                        numbers += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if total > 0 && numbers * 2 > total {
+            Align::Right
+        } else {
+            Align::Left
+        }
+    }
+}
+
+/// Rows of an `ORG_TABLE` node and their range in the document
+#[derive(Debug, Clone)]
+pub struct TableRange {
+    pub range: TextRange,
+    /// text of rows, the indentation of the first row may not be included
+    pub original: String,
+    /// indentation preceding the first row, outside of `range`
+    prefix: String,
+    pub table: Table,
+}
+
+impl TableRange {
+    pub fn new(node: &SyntaxNode) -> Option<TableRange> {
+        let mut rows = node.children().filter(|n| {
+            n.kind() == SyntaxKind::ORG_TABLE_STANDARD_ROW
+                || n.kind() == SyntaxKind::ORG_TABLE_RULE_ROW
+        });
+
+        let first = rows.next()?;
+        let last = rows.last().unwrap_or_else(|| first.clone());
+
+        // indentation may belong to previous tokens
+        let mut prefix = String::new();
+        let mut token = first.first_token().and_then(|t| t.prev_token());
+        while let Some(t) = token {
+            match t.text().rfind('\n') {
+                Some(i) => {
+                    prefix.insert_str(0, &t.text()[i + 1..]);
+                    break;
+                }
+                None => {
+                    prefix.insert_str(0, t.text());
+                    token = t.prev_token();
+                }
+            }
+        }
+
+        if !prefix.trim().is_empty() {
+            return None;
+        }
+
+        let range = TextRange::new(first.text_range().start(), last.text_range().end());
+
+        let text = node.to_string();
+        let offset = node.text_range().start();
+        let original = text[usize::from(range.start() - offset)..usize::from(range.end() - offset)]
+            .to_string();
+
+        Some(TableRange {
+            table: Table::parse(&format!("{prefix}{original}"))?,
+            range,
+            original,
+            prefix,
+        })
+    }
+
+    /// Returns the row and column at given offset, offsets outside of rows
+    /// are clamped to the first or last row
+    pub fn cell_at(&self, offset: TextSize) -> (usize, usize) {
+        let offset = offset.clamp(self.range.start(), self.range.end());
+        let before = &self.original[..usize::from(offset - self.range.start())];
+
+        let row = before
+            .matches('\n')
+            .count()
+            .min(self.table.rows.len().saturating_sub(1));
+        let line = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+        let column = line
+            .matches('|')
+            .count()
+            .saturating_sub(1)
+            .min(self.table.columns().saturating_sub(1));
+
+        (row, column)
+    }
+
+    /// Renders given table as replacement of `range`
+    pub fn replacement(&self, table: &Table) -> String {
+        let mut output = table.render();
+
+        if !self.original.ends_with('\n') {
+            output.pop();
+        }
+
+        output.split_off(self.prefix.len().min(output.len()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKind {
+    /// case-insensitive
+    Alphabetic,
+    /// cells that aren't numbers are treated as zero
+    Numeric,
+    /// timestamps like `<2024-01-02 Tue 10:00>` or durations like `1:30`
+    Time,
+}
+
+impl SortKind {
+    fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            SortKind::Alphabetic => a.to_lowercase().cmp(&b.to_lowercase()),
+            SortKind::Numeric => {
+                let number = |s: &str| s.replace(',', "").parse::<f64>().unwrap_or_default();
+                number(a).total_cmp(&number(b))
+            }
+            SortKind::Time => time_key(a).total_cmp(&time_key(b)),
+        }
+    }
+}
+
+/// Seconds of the first timestamp or duration in the cell, zero if there's none
+fn time_key(cell: &str) -> f64 {
+    for (i, _) in cell.char_indices() {
+        let rest = &cell[i..];
+
+        if rest.len() < 10 {
+            break;
+        }
+
+        let Some(date) = rest.get(..10) else {
+            continue;
+        };
+
+        let Ok(date) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+            continue;
+        };
+
+        // skip day name, e.g. `Tue`
+        let time = rest[10..]
+            .split_whitespace()
+            .take(2)
+            .find_map(|word| {
+                let word = word.trim_end_matches(['>', ']']);
+                chrono::NaiveTime::parse_from_str(word.get(..5).unwrap_or(word), "%H:%M").ok()
+            })
+            .unwrap_or_default();
+
+        return NaiveDateTime::new(date, time).and_utc().timestamp() as f64;
+    }
+
+    // durations like `1:30` or `1:30:15`
+    let mut seconds = 0.0;
+    for (i, part) in cell.trim().split(':').enumerate() {
+        match part.parse::<f64>() {
+            Ok(value) if i < 3 => seconds = seconds * 60.0 + value,
+            _ => return 0.0,
+        }
+    }
+    seconds
+        * if cell.matches(':').count() == 1 {
+            60.0
+        } else {
+            1.0
+        }
+}
+
+/// Splits tab-separated, comma-separated or whitespace-separated lines into cells
+pub fn split_separated(text: &str) -> Vec<Vec<String>> {
+    let lines = text.lines().filter(|line| !line.trim().is_empty());
+
+    if text.contains('\t') {
+        lines
+            .map(|line| line.split('\t').map(|c| c.trim().to_string()).collect())
+            .collect()
+    } else if text.contains(',') {
+        lines.map(split_csv_line).collect()
+    } else {
+        lines
+            .map(|line| line.split_whitespace().map(String::from).collect())
+            .collect()
+    }
+}
+
+/// Splits a line of CSV, quotes are removed and `""` is unescaped
+pub fn split_csv_line(line: &str) -> Vec<String> {
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut in_quote = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quote && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => in_quote = !in_quote,
+            ',' if !in_quote => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            c => cell.push(c),
+        }
+    }
+
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+/// Returns true if the row consists of width and alignment cookies only
+pub fn is_cookie_row(cells: &[String]) -> bool {
+    cells.iter().any(|cell| parse_cookie(cell).is_some())
+        && cells
+            .iter()
+            .all(|cell| cell.is_empty() || parse_cookie(cell).is_some())
+}
+
+/// Parses `<l>`, `<r>`, `<c>`, `<N>` and combinations like `<l10>`
+pub fn parse_cookie(cell: &str) -> Option<Cookie> {
+    let inner = cell.strip_prefix('<')?.strip_suffix('>')?;
+
+    let (align, width) = match inner.chars().next()? {
+        'l' => (Some(Align::Left), &inner[1..]),
+        'r' => (Some(Align::Right), &inner[1..]),
+        'c' => (Some(Align::Center), &inner[1..]),
+        _ => (None, inner),
+    };
+
+    let width = if width.is_empty() {
+        None
+    } else {
+        Some(width.parse().ok()?)
+    };
+
+    (align.is_some() || width.is_some()).then_some(Cookie { align, width })
+}
+
+/// Numbers like `42`, `-1.5`, `1e3`, `12%` or `1,000`
+pub fn is_number(cell: &str) -> bool {
+    let s = cell.trim();
+    let s = s.strip_suffix('%').unwrap_or(s);
+    let s = s.strip_prefix(['-', '+']).unwrap_or(s);
+
+    s.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && s.replace(',', "").parse::<f64>().is_ok()
+}
+
+#[test]
+fn test() {
+    let table = Table::parse("  | name | qty |\n|-\n| 苹果 | 10 |\n| pear|2.5|\n|<c>|  |\n| a |\n")
+        .unwrap();
+
+    assert_eq!(table.indent, "  ");
+    assert_eq!(table.columns(), 2);
+    assert_eq!(table.rows[1], TableRow::Rule);
+
+    assert_eq!(
+        table.render(),
+        r#"  | name | qty |
+  |------+-----|
+  | 苹果 |  10 |
+  | pear | 2.5 |
+  | <c>  |     |
+  |  a   |     |
+"#
+    );
+
+    assert_eq!(
+        parse_cookie("<r10>"),
+        Some(Cookie {
+            align: Some(Align::Right),
+            width: Some(10)
+        })
+    );
+    assert_eq!(
+        Table::parse("| | <r10> |\n| b | ccc |\n").unwrap().render(),
+        "|   | <r10> |\n| b |   ccc |\n"
+    );
+    assert_eq!(
+        Table::parse("| <3> |\n| abcdef |\n").unwrap().render(),
+        "| <3>    |\n| abcdef |\n"
+    );
+    assert_eq!(parse_cookie("<x>"), None);
+    assert_eq!(parse_cookie("<>"), None);
+
+    assert!(is_number("-1,000.5"));
+    assert!(is_number("12%"));
+    assert!(!is_number("1a"));
+    assert!(!is_number("inf"));
+}
+
+#[test]
+fn edit() {
+    use orgize::{ast::OrgTable, rowan::ast::AstNode, Org};
+
+    let org = Org::parse(
+        "| name | date             |\n|-\n| b | <2024-03-01 Fri> |\n| a | 2024-01-15 |\n",
+    );
+    let range = TableRange::new(org.first_node::<OrgTable>().unwrap().syntax()).unwrap();
+
+    assert_eq!(range.cell_at(TextSize::new(45)), (2, 1));
+    assert_eq!(range.cell_at(TextSize::new(3)), (0, 0));
+
+    let mut table = range.table.clone();
+    table.sort(2, 1, SortKind::Time, false);
+    assert_eq!(
+        table.render(),
+        "| name | date             |\n|------+------------------|\n| a    | 2024-01-15       |\n| b    | <2024-03-01 Fri> |\n"
+    );
+
+    table.sort(3, 0, SortKind::Alphabetic, true);
+    table.insert_column(1);
+    table.remove_column(0);
+    table.transpose();
+    assert_eq!(
+        table.render(),
+        "|      |                  |            |\n| date | <2024-03-01 Fri> | 2024-01-15 |\n"
+    );
+
+    assert_eq!(time_key("1:30"), 5400.0);
+    assert!(SortKind::Numeric.compare("1,000", "20").is_gt());
+
+    assert_eq!(
+        split_separated("a,\"b, c\"\n\nd,e\n"),
+        vec![vec!["a", "b, c"], vec!["d", "e"]]
+    );
+}
@@ -0,0 +1,758 @@
+use orgize::SyntaxNode;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::utils::table::{is_cookie_row, Table, TableRow};
+
+/// Formulas of `#+TBLFM:` lines following the table, separated by `::`
+pub fn tblfm_lines(table: &SyntaxNode) -> Vec<String> {
+    let text = table.to_string();
+
+    let mut formulas: Vec<_> = text.lines().filter_map(strip_tblfm).collect();
+
+    // formulas may be parsed as keywords following the table
+    if formulas.is_empty() && !text.ends_with("\n\n") {
+        let mut sibling = table.next_sibling();
+
+        while let Some(node) = sibling {
+            let text = node.to_string();
+
+            let Some(line) = strip_tblfm(text.trim_end()) else {
+                break;
+            };
+            formulas.push(line);
+
+            if text.trim_end().contains('\n') || text.ends_with("\n\n") {
+                break;
+            }
+            sibling = node.next_sibling();
+        }
+    }
+
+    formulas
+}
+
+fn strip_tblfm(line: &str) -> Option<String> {
+    let line = line.trim_start();
+    let prefix = line.get(..8)?;
+    prefix
+        .eq_ignore_ascii_case("#+TBLFM:")
+        .then(|| line[8..].trim().to_string())
+}
+
+/// Parses and evaluates formulas, updating cells of the table
+///
+/// Column formulas are applied to rows below the first rule, then field
+/// formulas. Fields failing to evaluate are set to `#ERROR`, formulas failing
+/// to parse or referencing fields out of the table are skipped and returned
+/// as errors.
+pub fn recalculate(table: &mut Table, tblfm: &[String]) -> Vec<anyhow::Error> {
+    let mut formulas = vec![];
+    let mut errors = vec![];
+
+    for formula in tblfm.iter().flat_map(|line| line.split("::")) {
+        let formula = formula.trim();
+        if !formula.is_empty() {
+            match Formula::parse(formula) {
+                Ok(formula) => formulas.push(formula),
+                Err(err) => errors.push(err),
+            }
+        }
+    }
+
+    // field formulas take precedence over column formulas
+    formulas.sort_by_key(|formula| matches!(formula.target, Target::Field(..)));
+
+    let first_rule = table
+        .rows
+        .iter()
+        .position(|row| *row == TableRow::Rule)
+        .unwrap_or(0);
+
+    for formula in &formulas {
+        if let Err(err) = formula.apply(table, first_rule) {
+            errors.push(err.context(format!("formula {:?}", formula.input)));
+        }
+    }
+
+    errors
+}
+
+fn set_cell(table: &mut Table, row: usize, column: usize, value: String) {
+    if let Some(TableRow::Cells(cells)) = table.rows.get_mut(row) {
+        if cells.len() <= column {
+            cells.resize(column + 1, String::new());
+        }
+        cells[column] = value;
+    }
+}
+
+impl Formula {
+    fn apply(&self, table: &mut Table, first_rule: usize) -> anyhow::Result<()> {
+        match &self.target {
+            Target::Column(column) => {
+                let column = resolve_column(table, column, 0)?;
+
+                let rows: Vec<_> = table
+                    .rows
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, row)| {
+                        *index >= first_rule
+                            && matches!(row, TableRow::Cells(cells) if !is_cookie_row(cells))
+                    })
+                    .map(|(index, _)| index)
+                    .collect();
+
+                for row in rows {
+                    let value = self.evaluate(table, row, column);
+                    set_cell(table, row, column, value);
+                }
+            }
+            Target::Field(row, column) => {
+                let row = resolve_row(table, row, 0)?;
+                let column = resolve_column(table, column, 0)?;
+                let value = self.evaluate(table, row, column);
+                set_cell(table, row, column, value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RowRef {
+    /// `@3`, one-based, rules are not counted
+    Absolute(usize),
+    /// `@-1` or `@+1`
+    Relative(isize),
+    /// `@<`
+    First,
+    /// `@>`
+    Last,
+    /// `@I`, `@II`, ..., the n-th rule
+    Rule(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ColumnRef {
+    /// `$3`, one-based
+    Absolute(usize),
+    /// `$-1` or `$+1`
+    Relative(isize),
+    /// `$<`
+    First,
+    /// `$>`
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FieldRef {
+    /// `None` refers to the current row
+    row: Option<RowRef>,
+    /// `None` refers to the current column
+    column: Option<ColumnRef>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Field(FieldRef),
+    Range(FieldRef, FieldRef),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Column(ColumnRef),
+    Field(RowRef, ColumnRef),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Formula {
+    input: String,
+    target: Target,
+    expr: Expr,
+    /// printf-like format, e.g. `%.2f`
+    format: Option<NumberFormat>,
+}
+
+/// `%[flags][width][.precision]conversion`
+#[derive(Debug, Clone, Default, PartialEq)]
+struct NumberFormat {
+    /// `0` flag, pads with zeros instead of spaces
+    zero: bool,
+    /// `+` flag
+    plus: bool,
+    width: usize,
+    precision: Option<usize>,
+    /// one of `f`, `d`, `e` and `g`
+    conversion: char,
+}
+
+impl NumberFormat {
+    fn parse(input: &str) -> Option<NumberFormat> {
+        let mut format = NumberFormat::default();
+        let mut chars = input.strip_prefix('%')?.chars().peekable();
+
+        while let Some(flag @ ('-' | '+' | ' ' | '#' | '0')) = chars.peek().copied() {
+            format.zero |= flag == '0';
+            format.plus |= flag == '+';
+            chars.next();
+        }
+
+        format.width = parse_digits(&mut chars).unwrap_or(0);
+
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            format.precision = Some(parse_digits(&mut chars).unwrap_or(0));
+        }
+
+        format.conversion = chars
+            .next()
+            .filter(|c| matches!(c, 'f' | 'd' | 'e' | 'g'))?;
+
+        chars.next().is_none().then_some(format)
+    }
+}
+
+fn parse_digits(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut number = None;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        number = Some(number.unwrap_or(0) * 10 + digit as usize);
+        chars.next();
+    }
+    number
+}
+
+impl Formula {
+    fn parse(input: &str) -> anyhow::Result<Formula> {
+        let Some((lhs, rhs)) = input.split_once('=') else {
+            anyhow::bail!("invalid formula {input:?}");
+        };
+
+        let mut parser = Parser::new(lhs.trim());
+        let target = match parser.field_ref().filter(|_| parser.is_eof()) {
+            Some(FieldRef {
+                row: None,
+                column: Some(column),
+            }) => Target::Column(column),
+            Some(FieldRef {
+                row: Some(row),
+                column: Some(column),
+            }) => Target::Field(row, column),
+            _ => anyhow::bail!("unsupported formula target {lhs:?}"),
+        };
+
+        let (expr, format) = match rhs.split_once(';') {
+            Some((expr, format)) => (expr, Some(format.trim())),
+            None => (rhs, None),
+        };
+
+        if expr.trim_start().starts_with('\'') {
+            anyhow::bail!("emacs lisp formulas are not supported: {input:?}");
+        }
+
+        let mut parser = Parser::new(expr.trim());
+        let expr = parser
+            .expr()
+            .filter(|_| parser.is_eof())
+            .ok_or_else(|| anyhow::anyhow!("failed to parse formula {input:?}"))?;
+
+        // mode flags like `N` or `E` are ignored
+        let format = match format.and_then(|format| format.find('%').map(|i| &format[i..])) {
+            Some(format) => Some(
+                NumberFormat::parse(format)
+                    .ok_or_else(|| anyhow::anyhow!("unsupported format {format:?} in {input:?}"))?,
+            ),
+            None => None,
+        };
+
+        Ok(Formula {
+            input: input.to_string(),
+            target,
+            expr,
+            format,
+        })
+    }
+
+    fn evaluate(&self, table: &Table, row: usize, column: usize) -> String {
+        match evaluate(&self.expr, table, row, column) {
+            Ok(Value::Scalar(value)) => format_number(value, self.format.as_ref()),
+            Ok(Value::Vector(_)) | Err(_) => "#ERROR".into(),
+        }
+    }
+}
+
+enum Value {
+    Scalar(f64),
+    Vector(Vec<f64>),
+}
+
+fn evaluate(expr: &Expr, table: &Table, row: usize, column: usize) -> anyhow::Result<Value> {
+    let scalar = |expr: &Expr| -> anyhow::Result<f64> {
+        match evaluate(expr, table, row, column)? {
+            Value::Scalar(value) => Ok(value),
+            Value::Vector(_) => anyhow::bail!("ranges are only allowed in functions"),
+        }
+    };
+
+    Ok(match expr {
+        Expr::Number(value) => Value::Scalar(*value),
+        Expr::Field(field) => {
+            let row = match &field.row {
+                Some(r) => resolve_row(table, r, row)?,
+                None => row,
+            };
+            let column = match &field.column {
+                Some(c) => resolve_column(table, c, column)?,
+                None => column,
+            };
+            Value::Scalar(cell_value(table, row, column)?)
+        }
+        Expr::Range(from, to) => {
+            let resolve = |field: &FieldRef| -> anyhow::Result<(usize, usize)> {
+                Ok((
+                    match &field.row {
+                        Some(r) => resolve_row(table, r, row)?,
+                        None => row,
+                    },
+                    match &field.column {
+                        Some(c) => resolve_column(table, c, column)?,
+                        None => column,
+                    },
+                ))
+            };
+
+            let (r1, c1) = resolve(from)?;
+            let (r2, c2) = resolve(to)?;
+
+            let mut values = vec![];
+            for r in r1.min(r2)..=r1.max(r2) {
+                if !matches!(table.rows[r], TableRow::Cells(_)) {
+                    continue;
+                }
+                for c in c1.min(c2)..=c1.max(c2) {
+                    // empty fields are skipped in ranges
+                    if !cell(table, r, c).is_empty() {
+                        values.push(cell_value(table, r, c)?);
+                    }
+                }
+            }
+            Value::Vector(values)
+        }
+        Expr::Neg(expr) => Value::Scalar(-scalar(expr)?),
+        Expr::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (scalar(lhs)?, scalar(rhs)?);
+            Value::Scalar(match op {
+                '+' => lhs + rhs,
+                '-' => lhs - rhs,
+                '*' => lhs * rhs,
+                '/' if rhs == 0.0 => anyhow::bail!("division by zero"),
+                '/' => lhs / rhs,
+                '^' => lhs.powf(rhs),
+                _ => unreachable!(),
+            })
+        }
+        Expr::Call(name, args) => {
+            let mut values = vec![];
+            for arg in args {
+                match evaluate(arg, table, row, column)? {
+                    Value::Scalar(value) => values.push(value),
+                    Value::Vector(vector) => values.extend(vector),
+                }
+            }
+
+            Value::Scalar(match name.as_str() {
+                "vsum" => values.iter().sum(),
+                "vmean" if values.is_empty() => anyhow::bail!("vmean of empty vector"),
+                "vmean" => values.iter().sum::<f64>() / values.len() as f64,
+                "vmin" => values.into_iter().reduce(f64::min).unwrap_or_default(),
+                "vmax" => values.into_iter().reduce(f64::max).unwrap_or_default(),
+                _ => anyhow::bail!("unsupported function {name:?}"),
+            })
+        }
+    })
+}
+
+/// Returns the index of referenced row in `table.rows`
+fn resolve_row(table: &Table, row: &RowRef, current: usize) -> anyhow::Result<usize> {
+    let data: Vec<_> = table
+        .rows
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| matches!(row, TableRow::Cells(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    let found = match row {
+        RowRef::Absolute(n) => n.checked_sub(1).and_then(|n| data.get(n)).copied(),
+        RowRef::First => data.first().copied(),
+        RowRef::Last => data.last().copied(),
+        RowRef::Relative(offset) => data
+            .iter()
+            .position(|index| *index == current)
+            .and_then(|position| position.checked_add_signed(*offset))
+            .and_then(|position| data.get(position))
+            .copied(),
+        RowRef::Rule(n) => table
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| **row == TableRow::Rule)
+            .nth(n.saturating_sub(1))
+            .map(|(index, _)| index),
+    };
+
+    found.ok_or_else(|| anyhow::anyhow!("row reference {row:?} is out of range"))
+}
+
+/// Returns the zero-based index of referenced column
+fn resolve_column(table: &Table, column: &ColumnRef, current: usize) -> anyhow::Result<usize> {
+    let columns = table.columns();
+
+    let found = match column {
+        ColumnRef::Absolute(n) => n.checked_sub(1).filter(|column| *column < columns),
+        ColumnRef::First => Some(0),
+        ColumnRef::Last => columns.checked_sub(1),
+        ColumnRef::Relative(offset) => current
+            .checked_add_signed(*offset)
+            .filter(|column| *column < columns),
+    };
+
+    found.ok_or_else(|| anyhow::anyhow!("column reference {column:?} is out of range"))
+}
+
+fn cell(table: &Table, row: usize, column: usize) -> &str {
+    match table.rows.get(row) {
+        Some(TableRow::Cells(cells)) => cells.get(column).map(String::as_str).unwrap_or_default(),
+        _ => "",
+    }
+}
+
+/// Empty fields are treated as zero
+fn cell_value(table: &Table, row: usize, column: usize) -> anyhow::Result<f64> {
+    let cell = cell(table, row, column);
+
+    if cell.is_empty() {
+        return Ok(0.0);
+    }
+
+    cell.replace(',', "")
+        .parse()
+        .map_err(|_| anyhow::anyhow!("{cell:?} is not a number"))
+}
+
+/// Formats with printf-like format, or up to 12 decimal places
+///
+/// Padding with spaces is dropped, since cells are trimmed when the table is
+/// aligned, like in org-mode.
+fn format_number(value: f64, format: Option<&NumberFormat>) -> String {
+    if let Some(format) = format {
+        let precision = format.precision.unwrap_or(6);
+
+        let formatted = match format.conversion {
+            'd' => format!("{}", value.round() as i64),
+            'e' => format!("{:.*e}", precision, value),
+            'g' => format_number(value, None),
+            _ => format!("{:.*}", precision, value),
+        };
+
+        let (sign, digits) = match formatted.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None if format.plus => ("+", formatted.as_str()),
+            None => ("", formatted.as_str()),
+        };
+
+        let padding = format.width.saturating_sub(sign.len() + digits.len());
+
+        return if format.zero && padding > 0 {
+            format!("{sign}{}{digits}", "0".repeat(padding))
+        } else {
+            format!("{sign}{digits}")
+        };
+    }
+
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{}", value as i64);
+    }
+
+    let formatted = format!("{value:.12}");
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser { input, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn is_eof(&mut self) -> bool {
+        self.skip_whitespace();
+        self.pos == self.input.len()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// `term (('+' | '-') term)*`
+    fn expr(&mut self) -> Option<Expr> {
+        let mut lhs = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+        Some(lhs)
+    }
+
+    /// `unary (('*' | '/') unary)*`
+    fn term(&mut self) -> Option<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        Some(lhs)
+    }
+
+    /// `'-' unary | power`
+    fn unary(&mut self) -> Option<Expr> {
+        if self.eat('-') {
+            return Some(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    /// `atom ('^' unary)?`
+    fn power(&mut self) -> Option<Expr> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Some(Expr::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Some(base)
+    }
+
+    fn atom(&mut self) -> Option<Expr> {
+        match self.peek()? {
+            '(' => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.eat(')').then_some(expr)
+            }
+            '@' | '$' => {
+                let from = self.field_ref()?;
+                if self.rest().starts_with("..") {
+                    self.pos += 2;
+                    let to = self.field_ref()?;
+                    Some(Expr::Range(from, to))
+                } else {
+                    Some(Expr::Field(from))
+                }
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let rest = self.rest();
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit() && c != '.')
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                rest[..len].parse().ok().map(Expr::Number)
+            }
+            c if c.is_ascii_alphabetic() => {
+                let rest = self.rest();
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                let name = rest[..len].to_string();
+
+                if !self.eat('(') {
+                    return None;
+                }
+
+                let mut args = vec![];
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return None;
+                        }
+                    }
+                }
+                Some(Expr::Call(name, args))
+            }
+            _ => None,
+        }
+    }
+
+    /// `@row$column`, `@row` or `$column`
+    fn field_ref(&mut self) -> Option<FieldRef> {
+        self.skip_whitespace();
+
+        let row = if self.rest().starts_with('@') {
+            self.pos += 1;
+            Some(self.row_ref()?)
+        } else {
+            None
+        };
+
+        let column = if self.rest().starts_with('$') {
+            self.pos += 1;
+            Some(self.column_ref()?)
+        } else {
+            None
+        };
+
+        (row.is_some() || column.is_some()).then_some(FieldRef { row, column })
+    }
+
+    fn row_ref(&mut self) -> Option<RowRef> {
+        let rest = self.rest();
+
+        if rest.starts_with('I') {
+            let len = rest.find(|c| c != 'I').unwrap_or(rest.len());
+            self.pos += len;
+            return Some(RowRef::Rule(len));
+        }
+
+        match self.reference()? {
+            Reference::Absolute(n) => Some(RowRef::Absolute(n)),
+            Reference::Relative(n) => Some(RowRef::Relative(n)),
+            Reference::First => Some(RowRef::First),
+            Reference::Last => Some(RowRef::Last),
+        }
+    }
+
+    fn column_ref(&mut self) -> Option<ColumnRef> {
+        match self.reference()? {
+            Reference::Absolute(n) => Some(ColumnRef::Absolute(n)),
+            Reference::Relative(n) => Some(ColumnRef::Relative(n)),
+            Reference::First => Some(ColumnRef::First),
+            Reference::Last => Some(ColumnRef::Last),
+        }
+    }
+
+    fn reference(&mut self) -> Option<Reference> {
+        let rest = self.rest();
+
+        if rest.starts_with('<') {
+            self.pos += 1;
+            return Some(Reference::First);
+        }
+        if rest.starts_with('>') {
+            self.pos += 1;
+            return Some(Reference::Last);
+        }
+
+        let sign = match rest.chars().next()? {
+            '-' => Some(-1),
+            '+' => Some(1),
+            _ => None,
+        };
+        let digits = &rest[sign.map_or(0, |_| 1)..];
+        let len = digits
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(digits.len());
+        let n: usize = digits[..len].parse().ok()?;
+        self.pos += len + sign.map_or(0, |_| 1);
+
+        Some(match sign {
+            Some(sign) => Reference::Relative(sign * n as isize),
+            None => Reference::Absolute(n),
+        })
+    }
+}
+
+enum Reference {
+    Absolute(usize),
+    Relative(isize),
+    First,
+    Last,
+}
+
+#[test]
+fn test() {
+    let mut table = Table::parse(
+        r#"| item  | qty | price | total |
+|-------+-----+-------+-------|
+| apple |   2 |   1.5 |       |
+| pear  |   3 |  0.25 |       |
+|-------+-----+-------+-------|
+| sum   |     |       |       |
+"#,
+    )
+    .unwrap();
+
+    assert!(recalculate(
+        &mut table,
+        &[
+            "$4=$2*$3;%.2f".into(),
+            "@>$4=vsum(@I..@II)::@>$2=vmax(@2$2..@-1$2)".into(),
+        ],
+    )
+    .is_empty());
+
+    assert_eq!(
+        table.render(),
+        r#"| item  | qty | price | total |
+|-------+-----+-------+-------|
+| apple |   2 |   1.5 |  3.00 |
+| pear  |   3 |  0.25 |  0.75 |
+|-------+-----+-------+-------|
+| sum   |   3 |       |  3.75 |
+"#
+    );
+
+    assert_eq!(format_number(0.1 + 0.2, None), "0.3");
+    assert_eq!(format_number(2.0, None), "2");
+    let format = |format: &str, value: f64| {
+        format_number(value, Some(&NumberFormat::parse(format).unwrap()))
+    };
+    assert_eq!(format("%.1f", 2.345), "2.3");
+    assert_eq!(format("%5.2f", 2.345), "2.35");
+    assert_eq!(format("%06.2f", -2.345), "-02.35");
+    assert_eq!(format("%+d", 2.5), "+3");
+    assert!(NumberFormat::parse("%5.2x").is_none());
+
+    assert!(Formula::parse("$3='(+ 1 2)").is_err());
+    assert!(Formula::parse("$3=1 +").is_err());
+    assert!(Formula::parse("$3=1;%5.2q").is_err());
+
+    // out of range and invalid formulas are reported, others still apply
+    let mut table = Table::parse("| 1 | |\n| 2 | |\n").unwrap();
+    let errors = recalculate(&mut table, &["$9=1::$3='(+ 1 2)::$2=$1*2".into()]);
+    assert_eq!(errors.len(), 2);
+    assert_eq!(table.render(), "| 1 | 2 |\n| 2 | 4 |\n");
+}
@@ -0,0 +1,335 @@
+use orgize::{SyntaxKind, SyntaxNode};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::utils::src_block::{block_name, split_header_args};
+
+/// Value of a `:var` binding
+#[derive(Debug, Clone, PartialEq)]
+pub enum VarValue {
+    String(String),
+    Number(String),
+    List(Vec<String>),
+    Table(Vec<Vec<String>>),
+}
+
+/// Right hand side of `:var name=value`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Var {
+    Literal(VarValue),
+    /// named table, list or source block; blocks are always evaluated
+    Reference(String),
+    /// `block(n=2)`, arguments override `:var` of the block
+    Call {
+        name: String,
+        args: Vec<(String, Var)>,
+    },
+}
+
+/// Parses all `:var` bindings in header arguments, later ones override earlier ones
+pub fn parse_vars(args: &[&str]) -> Vec<(String, Var)> {
+    let mut vars: Vec<(String, Var)> = vec![];
+
+    for args in args {
+        for (_, value) in split_header_args(args)
+            .into_iter()
+            .filter(|(key, _)| *key == ":var")
+        {
+            for (name, var) in parse_assignments(value) {
+                if let Some(existing) = vars.iter_mut().find(|(n, _)| *n == name) {
+                    existing.1 = var;
+                } else {
+                    vars.push((name, var));
+                }
+            }
+        }
+    }
+
+    vars
+}
+
+/// Parses comma-separated `name=value`, e.g. arguments of `block(a=1, b=tbl)`
+pub fn parse_assignments(input: &str) -> Vec<(String, Var)> {
+    split_top_level(input)
+        .into_iter()
+        .filter_map(|assignment| {
+            let (name, value) = assignment.split_once('=')?;
+            let name = name.trim();
+            (!name.is_empty()).then(|| (name.to_string(), parse_var(value.trim())))
+        })
+        .collect()
+}
+
+fn parse_var(value: &str) -> Var {
+    if let Some(s) = value.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        return Var::Literal(VarValue::String(s.replace("\\\"", "\"")));
+    }
+
+    if is_number(value) {
+        return Var::Literal(VarValue::Number(value.to_string()));
+    }
+
+    if let Some((name, args)) = value.split_once('(') {
+        if let Some(args) = args.strip_suffix(')') {
+            return Var::Call {
+                name: name.trim().to_string(),
+                args: parse_assignments(args),
+            };
+        }
+    }
+
+    Var::Reference(value.to_string())
+}
+
+/// Identifies the evaluation of a block with given arguments, used for
+/// caching results, e.g. `blk` or `blk(n=2)`
+pub fn call_key(name: &str, args: &[(String, Var)]) -> String {
+    if args.is_empty() {
+        return name.to_string();
+    }
+
+    let args: Vec<_> = args
+        .iter()
+        .map(|(name, var)| format!("{name}={var}"))
+        .collect();
+
+    format!("{name}({})", args.join(", "))
+}
+
+/// Splits by comma, ignoring ones inside quotes or parentheses
+fn split_top_level(input: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut in_quote, mut start) = (0, false, 0);
+
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => in_quote = !in_quote,
+            '(' if !in_quote => depth += 1,
+            ')' if !in_quote => depth -= 1,
+            ',' if !in_quote && depth == 0 => {
+                parts.push(&input[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&input[start..]);
+    parts
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'-' | b'+' | b'e' | b'E'))
+        && s.parse::<f64>().is_ok()
+}
+
+/// Formats a binding as it would appear in `:var name=value`
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Var::Literal(VarValue::String(s)) => write!(f, "{s:?}"),
+            Var::Literal(VarValue::Number(n)) => f.write_str(n),
+            Var::Literal(VarValue::List(_)) => f.write_str("<list>"),
+            Var::Literal(VarValue::Table(_)) => f.write_str("<table>"),
+            Var::Reference(name) => f.write_str(name),
+            Var::Call { name, args } if args.is_empty() => write!(f, "{name}()"),
+            Var::Call { name, args } => f.write_str(&call_key(name, args)),
+        }
+    }
+}
+
+impl VarValue {
+    /// Converts evaluation output to value
+    pub fn from_output(output: &str) -> VarValue {
+        let output = output.trim_end_matches(['\n', '\r']);
+
+        if is_number(output.trim()) {
+            VarValue::Number(output.trim().to_string())
+        } else {
+            VarValue::String(output.to_string())
+        }
+    }
+
+    /// Reads named table or list
+    pub fn from_node(node: &SyntaxNode) -> Option<VarValue> {
+        match node.kind() {
+            SyntaxKind::ORG_TABLE => Some(VarValue::Table(
+                node.children()
+                    .filter(|n| n.kind() == SyntaxKind::ORG_TABLE_STANDARD_ROW)
+                    .map(|row| {
+                        let row = row.to_string();
+                        let row = row.trim();
+                        let row = row.strip_prefix('|').unwrap_or(row);
+                        let row = row.strip_suffix('|').unwrap_or(row);
+                        row.split('|').map(|c| c.trim().to_string()).collect()
+                    })
+                    .collect(),
+            )),
+            SyntaxKind::LIST => Some(VarValue::List(
+                node.children()
+                    .filter(|n| n.kind() == SyntaxKind::LIST_ITEM)
+                    .map(|item| {
+                        let item = item.to_string();
+                        let line = item.lines().next().unwrap_or_default().trim_start();
+                        // strip bullet
+                        let text = line
+                            .split_once(char::is_whitespace)
+                            .map(|(_, text)| text)
+                            .unwrap_or_default();
+                        text.trim().to_string()
+                    })
+                    .collect(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let cell = |s: &String| {
+            if is_number(s) {
+                serde_json::from_str(s).unwrap_or_else(|_| JsonValue::String(s.clone()))
+            } else {
+                JsonValue::String(s.clone())
+            }
+        };
+
+        match self {
+            VarValue::String(s) => JsonValue::String(s.clone()),
+            VarValue::Number(n) => cell(n),
+            VarValue::List(items) => JsonValue::Array(items.iter().map(cell).collect()),
+            VarValue::Table(rows) => JsonValue::Array(
+                rows.iter()
+                    .map(|row| JsonValue::Array(row.iter().map(cell).collect()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Text used by shells, tables become tab-separated lines
+    fn to_text(&self) -> String {
+        match self {
+            VarValue::String(s) | VarValue::Number(s) => s.clone(),
+            VarValue::List(items) => items.join("\n"),
+            VarValue::Table(rows) => rows
+                .iter()
+                .map(|row| row.join("\t"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Collects tables and lists with `#+NAME:`
+pub fn collect_named_data(document: &SyntaxNode) -> HashMap<String, VarValue> {
+    document
+        .descendants()
+        .filter(|n| n.kind() == SyntaxKind::ORG_TABLE || n.kind() == SyntaxKind::LIST)
+        .filter_map(|n| Some((block_name(&n)?, VarValue::from_node(&n)?)))
+        .collect()
+}
+
+/// Returns variable assignments to be prepended to the script
+pub fn assign_vars(language: &str, vars: &[(String, VarValue)]) -> anyhow::Result<String> {
+    let mut output = String::new();
+
+    for (name, value) in vars {
+        let _ = match language {
+            "js" | "javascript" | "ts" | "typescript" => {
+                writeln!(output, "const {name} = {};", value.to_json())
+            }
+            "py" | "python" | "ruby" => writeln!(output, "{name} = {}", value.to_json()),
+            "sh" | "bash" | "zsh" => match value {
+                VarValue::List(items) => writeln!(
+                    output,
+                    "{name}=({})",
+                    items
+                        .iter()
+                        .map(|i| shell_quote(i))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ),
+                _ => writeln!(output, "{name}={}", shell_quote(&value.to_text())),
+            },
+            "fish" => match value {
+                VarValue::List(items) => writeln!(
+                    output,
+                    "set {name} {}",
+                    items
+                        .iter()
+                        .map(|i| shell_quote(i))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ),
+                _ => writeln!(output, "set {name} {}", shell_quote(&value.to_text())),
+            },
+            _ => anyhow::bail!("`:var` is not supported for language {language:?}"),
+        };
+    }
+
+    Ok(output)
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[test]
+fn test() {
+    assert_eq!(
+        parse_vars(&[
+            r#":var a=1 :results output"#,
+            r#":var b="x, y", c=tbl :var d=blk(n=2)"#
+        ]),
+        vec![
+            ("a".into(), Var::Literal(VarValue::Number("1".into()))),
+            ("b".into(), Var::Literal(VarValue::String("x, y".into()))),
+            ("c".into(), Var::Reference("tbl".into())),
+            (
+                "d".into(),
+                Var::Call {
+                    name: "blk".into(),
+                    args: vec![("n".into(), Var::Literal(VarValue::Number("2".into())))],
+                }
+            ),
+        ]
+    );
+
+    assert_eq!(
+        parse_vars(&[r#":var x=blk(), y=blk(n=2, s="a, b")"#])
+            .iter()
+            .map(|(_, var)| var.to_string())
+            .collect::<Vec<_>>(),
+        vec!["blk()", r#"blk(n=2, s="a, b")"#]
+    );
+
+    assert_eq!(
+        parse_vars(&[":var a=1", ":var a=2"]),
+        vec![("a".into(), Var::Literal(VarValue::Number("2".into())))]
+    );
+
+    let vars = vec![
+        ("n".to_string(), VarValue::Number("1".into())),
+        (
+            "t".to_string(),
+            VarValue::Table(vec![vec!["a".into(), "2".into()]]),
+        ),
+        ("s".to_string(), VarValue::String("it's".into())),
+    ];
+
+    assert_eq!(
+        assign_vars("python", &vars).unwrap(),
+        "n = 1\nt = [[\"a\",2]]\ns = \"it's\"\n"
+    );
+    assert_eq!(
+        assign_vars("js", &vars).unwrap(),
+        "const n = 1;\nconst t = [[\"a\",2]];\nconst s = \"it's\";\n"
+    );
+    assert_eq!(
+        assign_vars("bash", &vars).unwrap(),
+        "n='1'\nt='a\t2'\ns='it'\\''s'\n"
+    );
+    assert!(assign_vars("elisp", &vars).is_err());
+}
@@ -6,10 +6,11 @@ use serde_json::Value;
 use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 
-use super::SERIALIZER;
+use super::{evaluate_js, has_method, SERIALIZER};
 use crate::backend::{Backend, Documents};
 use crate::command::OrgwiseCommand;
 use crate::lsp;
+use crate::utils::language::{ExecuteOutput, Executor};
 
 #[wasm_bindgen]
 extern "C" {
@@ -23,6 +24,14 @@ extern "C" {
 
     #[wasm_bindgen(method, js_name = "write", catch)]
     pub async fn write(this: &WasmMethods, path: &str, content: &str) -> Result<JsValue, JsValue>;
+
+    /// optional, javascript blocks are evaluated in place if missing
+    #[wasm_bindgen(method, catch)]
+    pub async fn execute(
+        this: &WasmMethods,
+        executable: &str,
+        content: &str,
+    ) -> Result<JsValue, JsValue>;
 }
 
 #[wasm_bindgen(js_name = "Backend")]
@@ -120,6 +129,32 @@ impl Backend for WasmBackend {
     fn documents(&self) -> &Documents {
         &self.documents
     }
+
+    async fn execute(&self, executor: &Executor, content: &str) -> anyhow::Result<ExecuteOutput> {
+        if !has_method(self.methods.as_ref(), "execute") {
+            if executor.extension == "js" {
+                return evaluate_js(content).await;
+            }
+
+            anyhow::bail!(
+                "cannot execute {:?}, no `execute` method is provided",
+                executor.command.join(" ")
+            );
+        }
+
+        let stdout = self
+            .methods
+            .execute(&executor.command.join(" "), content)
+            .await
+            .map(|value| value.as_string().unwrap_or_default())
+            .map_err(|err| anyhow::anyhow!("JS Error: {err:?}"))?;
+
+        Ok(ExecuteOutput {
+            stdout,
+            status: Some(0),
+            ..Default::default()
+        })
+    }
 }
 
 #[wasm_bindgen(js_class = "Backend")]
@@ -137,6 +172,11 @@ impl WasmBackend {
     #[wasm_bindgen(js_name = "setOptions")]
     pub fn set_options(&mut self, options: JsValue) {
         let options: lsp::InitializationOptions = serde_wasm_bindgen::from_value(options).unwrap();
+        lsp::set_id_locations_file(self, options.id_locations_file.as_deref());
+        lsp::set_languages(self, options.languages);
+        lsp::set_trusted_directories(self, options.trusted_directories);
+        lsp::set_library_files(self, options.library_files);
+        self.documents().set_default_timeout(options.timeout);
         self.documents().set_default_parse_config(ParseConfig {
             todo_keywords: (options.todo_keywords, options.done_keywords),
             ..Default::default()
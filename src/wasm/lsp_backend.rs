@@ -1,15 +1,16 @@
 use lsp_types::{
-    notification::*, request::*, ApplyWorkspaceEditParams, LogMessageParams, MessageType,
-    ShowMessageParams, TextEdit, Url, WorkspaceEdit,
+    notification::*, request::*, ApplyWorkspaceEditParams, LogMessageParams, MessageActionItem,
+    MessageType, ShowMessageParams, ShowMessageRequestParams, TextEdit, Url, WorkspaceEdit,
 };
 use orgize::rowan::TextRange;
 use serde::Serialize;
 use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 
-use super::SERIALIZER;
+use super::{evaluate_js, has_method, SERIALIZER};
 use crate::backend::{Backend, Documents};
 use crate::lsp;
+use crate::utils::language::{ExecuteOutput, Executor};
 
 #[wasm_bindgen]
 extern "C" {
@@ -34,6 +35,7 @@ extern "C" {
     #[wasm_bindgen(method, js_name = "sendNotification")]
     pub async fn send_notification(this: &LspClient, method: &str, params: JsValue);
 
+    /// optional, javascript blocks are evaluated in place if missing
     #[wasm_bindgen(method, catch)]
     pub async fn execute(
         this: &LspClient,
@@ -133,16 +135,59 @@ impl Backend for LspBackend {
         Ok(())
     }
 
+    async fn show_message_request(
+        &self,
+        typ: MessageType,
+        message: String,
+        actions: Vec<String>,
+    ) -> anyhow::Result<Option<String>> {
+        let action = self
+            .send_request::<ShowMessageRequest>(ShowMessageRequestParams {
+                typ,
+                message,
+                actions: Some(
+                    actions
+                        .into_iter()
+                        .map(|title| MessageActionItem {
+                            title,
+                            properties: HashMap::new(),
+                        })
+                        .collect(),
+                ),
+            })
+            .await?;
+
+        Ok(action.map(|action| action.title))
+    }
+
     fn documents(&self) -> &Documents {
         &self.documents
     }
 
-    async fn execute(&self, executable: &str, content: &str) -> anyhow::Result<String> {
-        self.client
-            .execute(executable, content)
+    async fn execute(&self, executor: &Executor, content: &str) -> anyhow::Result<ExecuteOutput> {
+        if !has_method(self.client.as_ref(), "execute") {
+            if executor.extension == "js" {
+                return evaluate_js(content).await;
+            }
+
+            anyhow::bail!(
+                "cannot execute {:?}, no `execute` method is provided",
+                executor.command.join(" ")
+            );
+        }
+
+        let stdout = self
+            .client
+            .execute(&executor.command.join(" "), content)
             .await
             .map(|value| value.as_string().unwrap_or_default())
-            .map_err(|err| anyhow::anyhow!("JS Error: {err:?}"))
+            .map_err(|err| anyhow::anyhow!("JS Error: {err:?}"))?;
+
+        Ok(ExecuteOutput {
+            stdout,
+            status: Some(0),
+            ..Default::default()
+        })
     }
 }
 
@@ -230,6 +275,10 @@ impl LspBackend {
             DidChangeTextDocument::METHOD => {
                 n::<DidChangeTextDocument>(self, params, lsp::did_change)
             }
+            DidSaveTextDocument::METHOD => n::<DidSaveTextDocument>(self, params, lsp::did_save),
+            DidChangeWatchedFiles::METHOD => {
+                n::<DidChangeWatchedFiles>(self, params, lsp::did_change_watched_files)
+            }
             _ => {}
         }
     }
@@ -1,6 +1,72 @@
 mod backend;
 mod lsp_backend;
 
+use serde::Deserialize;
 use serde_wasm_bindgen::Serializer;
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+
+use crate::utils::language::ExecuteOutput;
 
 pub const SERIALIZER: Serializer = Serializer::new().serialize_maps_as_objects(true);
+
+#[wasm_bindgen(inline_js = r#"
+export function hasMethod(object, name) {
+  return typeof object?.[name] === "function";
+}
+
+export async function evaluateJs(content) {
+  let stdout = "";
+  let stderr = "";
+  const format = (args) =>
+    args.map((a) => (typeof a === "string" ? a : JSON.stringify(a))).join(" ") + "\n";
+  const console = {
+    ...globalThis.console,
+    log: (...args) => (stdout += format(args)),
+    info: (...args) => (stdout += format(args)),
+    debug: (...args) => (stdout += format(args)),
+    warn: (...args) => (stderr += format(args)),
+    error: (...args) => (stderr += format(args)),
+  };
+  const AsyncFunction = (async () => {}).constructor;
+  const start = Date.now();
+  let status = 0;
+  try {
+    await new AsyncFunction("console", content)(console);
+  } catch (err) {
+    stderr += String((err && err.stack) || err) + "\n";
+    status = 1;
+  }
+  return { stdout, stderr, status, duration: Date.now() - start };
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = "hasMethod")]
+    fn has_method(object: &JsValue, name: &str) -> bool;
+
+    #[wasm_bindgen(js_name = "evaluateJs")]
+    async fn evaluate_js_in_browser(content: &str) -> JsValue;
+}
+
+#[derive(Deserialize)]
+struct JsOutput {
+    stdout: String,
+    stderr: String,
+    status: i32,
+    /// milliseconds
+    duration: u64,
+}
+
+/// Evaluates javascript in the current JS context, `console` output is
+/// captured as stdout and stderr
+async fn evaluate_js(content: &str) -> anyhow::Result<ExecuteOutput> {
+    let output: JsOutput = serde_wasm_bindgen::from_value(evaluate_js_in_browser(content).await)
+        .map_err(|err| anyhow::anyhow!("JS Error: {err:?}"))?;
+
+    Ok(ExecuteOutput {
+        stdout: output.stdout,
+        stderr: output.stderr,
+        status: Some(output.status),
+        duration: Duration::from_millis(output.duration),
+    })
+}
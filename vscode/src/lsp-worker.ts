@@ -29,9 +29,7 @@ connection.onRequest("initialize", async (params) => {
         return connection.sendRequest(method, params);
       },
 
-      execute: () => {
-        throw new Error("`execute` is not support in web extension");
-      },
+      // `execute` is omitted, so javascript blocks are evaluated in worker
 
       readToString: () => {
         throw new Error("`readToString` is not support in web extension");
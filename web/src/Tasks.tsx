@@ -127,6 +127,7 @@ const ListItem: React.FC<{ item: SearchResult }> = ({ item }) => {
             executeCommand("headline-duplicate", {
               url: item.url,
               line: item.line,
+              version: item.version,
             }).then(() => {
               mutate("headline-search");
             })
@@ -142,6 +143,7 @@ const ListItem: React.FC<{ item: SearchResult }> = ({ item }) => {
               executeCommand("clocking-stop", {
                 url: item.url,
                 line: item.line,
+                version: item.version,
               }).then(() => {
                 mutate("headline-search");
                 mutate("clocking-status");
@@ -157,6 +159,7 @@ const ListItem: React.FC<{ item: SearchResult }> = ({ item }) => {
               executeCommand("clocking-start", {
                 url: item.url,
                 line: item.line,
+                version: item.version,
               }).then(() => {
                 mutate("headline-search");
                 mutate("clocking-status");
@@ -174,6 +177,7 @@ const ListItem: React.FC<{ item: SearchResult }> = ({ item }) => {
             executeCommand("headline-remove", {
               url: item.url,
               line: item.line,
+              version: item.version,
             }).then(() => {
               mutate("headline-search");
             })
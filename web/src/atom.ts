@@ -7,6 +7,7 @@ export type SearchResult = {
   section_markdown?: string;
   url: string;
   line: number;
+  version: number;
   level: number;
   priority?: string;
   tags: string[];